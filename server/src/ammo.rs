@@ -0,0 +1,144 @@
+use std::{collections::HashMap, path::Path};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use shared::weapons::{BeamState, WeaponKind};
+
+use crate::GameState;
+
+/// Server copy of the client's `AmmoRules`, checked against every fire request.
+#[derive(Resource, Debug, Clone)]
+pub struct AmmoRules {
+    /// Bullets a player may have flying at once. Every projectile of a shot
+    /// counts, and a shot only goes through if all of them fit.
+    pub max_live_bullets: usize,
+    /// Seconds between two shots
+    pub fire_cooldown: f32,
+    /// Magazine size and seconds to refill it once empty, `None` for no reloading.
+    pub magazine: Option<(u32, f32)>,
+}
+
+impl Default for AmmoRules {
+    fn default() -> Self {
+        Self {
+            max_live_bullets: 5,
+            fire_cooldown: 0.2,
+            magazine: None,
+        }
+    }
+}
+
+/// Beams kept in the game state, for clients that missed a state.
+const BEAMS_LENGTH: usize = 16;
+/// The client's assets, which hold the weapons' own cooldowns.
+const ASSETS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+/// The part of a `*.weapon.ron` file the server checks shots against.
+#[derive(Deserialize)]
+struct WeaponFile {
+    fire_cooldown: f32,
+}
+
+/// Seconds between two shots of each weapon, read from the files the client
+/// loads its weapons from.
+#[derive(Resource, Debug, Default)]
+pub struct WeaponCooldowns(HashMap<WeaponKind, f32>);
+
+impl WeaponCooldowns {
+    pub fn load() -> Self {
+        let cooldowns = WeaponKind::ALL.into_iter().filter_map(|kind| {
+            let path = Path::new(ASSETS_DIR).join(kind.path());
+            let file = std::fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|text| {
+                    ron::from_str::<WeaponFile>(&text).map_err(|error| error.to_string())
+                });
+            match file {
+                Ok(file) => Some((kind, file.fire_cooldown)),
+                Err(error) => {
+                    eprintln!("no cooldown for {kind:?} from {}: {error}", path.display());
+                    None
+                }
+            }
+        });
+        Self(cooldowns.collect())
+    }
+
+    /// The weapon's own cooldown, 0 if its file could not be read.
+    pub fn get(&self, weapon: WeaponKind) -> f32 {
+        self.0.get(&weapon).copied().unwrap_or(0.)
+    }
+}
+
+/// Bullets of `owner`'s still flying that count against `AmmoRules::max_live_bullets`.
+/// Shrapnel does not, as on the client.
+pub fn live_bullets(game_state: &GameState, owner: u64) -> usize {
+    game_state
+        .bullets
+        .iter()
+        .filter(|bullet| bullet.owner == owner && bullet.weapon != WeaponKind::Shrapnel)
+        .count()
+}
+
+/// Passes on a beam the server let its owner fire.
+pub fn fire_beam(game_state: &mut GameState, beam: BeamState) {
+    let id = game_state.beams.last().map_or(0, |last| last.id + 1);
+    game_state.beams.push(BeamState { id, ..beam });
+    if game_state.beams.len() > BEAMS_LENGTH {
+        game_state.beams.remove(0);
+    }
+}
+
+/// Hands out bullet ids, so clients can tell a new bullet from one they have spawned.
+#[derive(Resource, Default)]
+pub struct BulletIds(u32);
+
+impl BulletIds {
+    pub fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(1);
+        self.0
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PlayerAmmo {
+    /// Server time the player may fire again
+    ready_at: f32,
+    rounds_fired: u32,
+    reloaded_at: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct AmmoLedger(pub HashMap<u64, PlayerAmmo>);
+
+impl AmmoRules {
+    /// Checks a shot of `projectiles` bullets at time `now` against the rules
+    /// and records it if allowed. Like the client, the gun then waits out the
+    /// longer of the match's cooldown and the `weapon_cooldown` of the weapon fired.
+    pub fn try_fire(
+        &self,
+        ammo: &mut PlayerAmmo,
+        live_bullets: usize,
+        projectiles: usize,
+        weapon_cooldown: f32,
+        now: f32,
+    ) -> bool {
+        if live_bullets + projectiles > self.max_live_bullets || now < ammo.ready_at {
+            return false;
+        }
+        if let Some((size, reload)) = self.magazine {
+            if ammo.rounds_fired >= size {
+                if now < ammo.reloaded_at {
+                    return false;
+                }
+                ammo.rounds_fired = 0;
+            }
+            ammo.rounds_fired += 1;
+            if ammo.rounds_fired == size {
+                ammo.reloaded_at = now + reload;
+            }
+        }
+        ammo.ready_at = now + weapon_cooldown.max(self.fire_cooldown);
+        true
+    }
+}
@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+use shared::protocol::MatchPhase;
+
+use crate::{ammo::AmmoLedger, lobby, rounds::RoundRules, GameState};
+
+/// Client ids from here up belong to bots, real clients never get this high.
+const FIRST_BOT_ID: u64 = 1 << 48;
+
+pub fn is_bot(client_id: u64) -> bool {
+    client_id >= FIRST_BOT_ID
+}
+
+/// Whether `client_id` may act for `player`: its own tank, and every bot
+/// when it is the host. The host's client plays the bots like local bots,
+/// so they drive, aim and bank their shots off walls the same way.
+pub fn drives(game_state: &GameState, client_id: u64, player: u64) -> bool {
+    player == client_id || (is_bot(player) && game_state.host == Some(client_id))
+}
+
+/// Bots the server seats for the host to play. The server only keeps their
+/// lobby entries, scores and tanks' last positions; the host's client does
+/// all their driving and shooting. Someone is always hosting while there
+/// are bots, as they leave with the last person.
+#[derive(Resource, Debug, Clone)]
+pub struct HostBotRules {
+    /// Bots join the lobby until it has this many players, 0 for no bots
+    pub fill_to: usize,
+}
+
+impl Default for HostBotRules {
+    fn default() -> Self {
+        Self {
+            fill_to: RoundRules::default().min_players,
+        }
+    }
+}
+
+/// Tops the lobby up with host bots until there are `HostBotRules::fill_to`
+/// players, and takes them out again as people join. Bots never play on
+/// their own. They join the match along with everyone else in the lobby.
+pub fn fill_host_bots_system(
+    rules: Res<HostBotRules>,
+    mut game_state: ResMut<GameState>,
+    mut ammo_ledger: ResMut<AmmoLedger>,
+) {
+    let humans = game_state
+        .lobby
+        .keys()
+        .filter(|client_id| !is_bot(**client_id))
+        .count();
+    let wanted = if humans == 0 {
+        0
+    } else {
+        rules.fill_to.saturating_sub(humans)
+    };
+    let mut bots: Vec<u64> = game_state
+        .lobby
+        .keys()
+        .copied()
+        .filter(|client_id| is_bot(*client_id))
+        .collect();
+    bots.sort();
+
+    // Bots only come and go between matches, unless everyone left
+    if humans > 0 && game_state.phase != MatchPhase::Lobby {
+        return;
+    }
+    for client_id in bots.split_off(wanted.min(bots.len())) {
+        lobby::leave(&mut game_state, client_id);
+        ammo_ledger.0.remove(&client_id);
+    }
+    while bots.len() < wanted {
+        let client_id = (FIRST_BOT_ID..)
+            .find(|client_id| !game_state.lobby.contains_key(client_id))
+            .unwrap_or(FIRST_BOT_ID);
+        let name = format!("Bot {}", client_id - FIRST_BOT_ID + 1);
+        lobby::join(&mut game_state, client_id, name);
+        bots.push(client_id);
+    }
+}
@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+use shared::deployables::{DeployableKind, DeployableState};
+
+use crate::GameState;
+
+/// A mine only goes off with a player other than its layer this close to
+/// where it was laid. Generous, the server sees tanks a little late.
+const DETONATE_RANGE: f32 = 80.;
+
+/// Server copy of the client's mine and barrier limits.
+#[derive(Resource, Debug, Clone)]
+pub struct DeployableRules {
+    pub max_mines: usize,
+    pub max_barriers: usize,
+    /// Seconds a barrier stands
+    pub barrier_ttl: f32,
+}
+
+impl Default for DeployableRules {
+    fn default() -> Self {
+        Self {
+            max_mines: 3,
+            max_barriers: 1,
+            barrier_ttl: 8.,
+        }
+    }
+}
+
+/// Hands out deployable ids, so clients can tell a new mine or barrier from one they have spawned.
+#[derive(Resource, Default)]
+pub struct DeployableIds(u32);
+
+impl DeployableIds {
+    pub fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(1);
+        self.0
+    }
+}
+
+impl DeployableRules {
+    /// Adds `deployable` for its owner unless they already have as many of its kind as allowed.
+    pub fn try_deploy(
+        &self,
+        game_state: &mut GameState,
+        deployable: DeployableState,
+        id: u32,
+        now: f32,
+    ) -> bool {
+        let limit = match deployable.kind {
+            DeployableKind::Mine => self.max_mines,
+            DeployableKind::Barrier => self.max_barriers,
+        };
+        let placed = game_state
+            .deployables
+            .iter()
+            .filter(|placed| placed.owner == deployable.owner && placed.kind == deployable.kind)
+            .count();
+        if placed >= limit {
+            return false;
+        }
+        game_state.deployables.push(DeployableState {
+            id,
+            placed_at: now,
+            ..deployable
+        });
+        true
+    }
+}
+
+/// Removes mine `id` if a player other than its layer is close enough to
+/// where it was laid to have set it off. Returns whether it went off.
+pub fn detonate(game_state: &mut GameState, id: u32) -> bool {
+    let Some(index) = game_state
+        .deployables
+        .iter()
+        .position(|placed| placed.id == id && placed.kind == DeployableKind::Mine)
+    else {
+        return false;
+    };
+    let mine = &game_state.deployables[index];
+    let set_off = game_state.players.iter().any(|(client_id, player)| {
+        *client_id != mine.owner
+            && game_state.alive.contains(client_id)
+            && player.position.distance(mine.position) <= DETONATE_RANGE
+    });
+    if set_off {
+        game_state.deployables.swap_remove(index);
+    }
+    set_off
+}
+
+pub fn expire_deployables_system(
+    time: Res<Time>,
+    rules: Res<DeployableRules>,
+    mut game_state: ResMut<GameState>,
+) {
+    let now = time.elapsed_secs();
+    game_state.deployables.retain(|placed| {
+        placed.kind != DeployableKind::Barrier || now - placed.placed_at < rules.barrier_ttl
+    });
+}
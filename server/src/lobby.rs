@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use shared::{
+    lobby::{LobbyEntry, LobbyRequest, MapChoice, TankClass},
+    protocol::{MatchPhase, PlayerScore},
+};
+
+use crate::{bots::is_bot, modes::assign_team, GameState, PlayerState};
+
+/// Colours players pick from, as indices into the client's palette.
+pub const PALETTE_SIZE: u8 = 8;
+/// Longest name a player can pick, in characters.
+const MAX_NAME_LENGTH: usize = 16;
+
+/// Where the match's maze seeds come from, reseeded from the host's
+/// settings at the start of every match.
+#[derive(Resource)]
+pub struct MazeRng(pub StdRng);
+
+impl Default for MazeRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+/// Lowest palette index nobody in the lobby has taken.
+fn free_color(lobby: &HashMap<u64, LobbyEntry>) -> u8 {
+    (0..PALETTE_SIZE)
+        .find(|color| !lobby.values().any(|entry| entry.color == *color))
+        .unwrap_or(0)
+}
+
+/// Puts a newly connected client, or a new bot, in the lobby. The first
+/// person in becomes the host. Bots are always ready.
+pub fn join(game_state: &mut GameState, client_id: u64, name: String) {
+    let joined = game_state
+        .lobby
+        .values()
+        .map(|entry| entry.joined + 1)
+        .max()
+        .unwrap_or(0);
+    let entry = LobbyEntry {
+        name,
+        color: free_color(&game_state.lobby),
+        class: TankClass::default(),
+        ready: is_bot(client_id),
+        joined,
+    };
+    game_state.lobby.insert(client_id, entry);
+    if game_state.host.is_none() && !is_bot(client_id) {
+        game_state.host = Some(client_id);
+    }
+}
+
+/// Takes a client out of the lobby and any match it is in. A leaving host
+/// hands over to whoever has been connected longest.
+pub fn leave(game_state: &mut GameState, client_id: u64) {
+    game_state.lobby.remove(&client_id);
+    game_state.players.remove(&client_id);
+    game_state.alive.remove(&client_id);
+    game_state.scores.remove(&client_id);
+    game_state.teams.remove(&client_id);
+    // Nobody is left to report them gone
+    game_state
+        .bullets
+        .retain(|bullet| bullet.owner != client_id);
+    if game_state.host == Some(client_id) {
+        // Clients pick their own ids, so they say nothing about who came first
+        game_state.host = game_state
+            .lobby
+            .iter()
+            .filter(|(client_id, _)| !is_bot(**client_id))
+            .min_by_key(|(_, entry)| entry.joined)
+            .map(|(client_id, _)| *client_id);
+    }
+}
+
+/// Applies what `client_id` asked for. Nothing changes once the match is
+/// under way, and new settings have everyone ready up again.
+pub fn handle_request(game_state: &mut GameState, client_id: u64, request: LobbyRequest) {
+    if game_state.phase != MatchPhase::Lobby {
+        return;
+    }
+    if let LobbyRequest::Settings(settings) = request {
+        if game_state.host != Some(client_id) {
+            return;
+        }
+        game_state.mode = settings.mode;
+        game_state.friendly_fire = settings.friendly_fire;
+        game_state.map = settings.map;
+        game_state.seed = settings.seed;
+        game_state.bot_difficulty = settings.bots;
+        for (client_id, entry) in game_state.lobby.iter_mut() {
+            entry.ready = is_bot(*client_id);
+        }
+        return;
+    }
+
+    let color_taken = |color: u8| {
+        game_state
+            .lobby
+            .iter()
+            .any(|(other, entry)| *other != client_id && entry.color == color)
+    };
+    if let LobbyRequest::Color(color) = request {
+        if color >= PALETTE_SIZE || color_taken(color) {
+            return;
+        }
+    }
+    let Some(entry) = game_state.lobby.get_mut(&client_id) else {
+        return;
+    };
+    match request {
+        LobbyRequest::Name(name) => {
+            let name: String = name
+                .chars()
+                .filter(|c| !c.is_control())
+                .take(MAX_NAME_LENGTH)
+                .collect();
+            if !name.trim().is_empty() {
+                entry.name = name.trim().to_string();
+            }
+        }
+        LobbyRequest::Color(color) => entry.color = color,
+        LobbyRequest::Class(class) => entry.class = class,
+        LobbyRequest::Ready(ready) => entry.ready = ready,
+        LobbyRequest::Settings(_) => {}
+    }
+}
+
+/// Whether the match can start: enough players, at least one of them a
+/// person, and everyone ready.
+pub fn all_ready(game_state: &GameState, min_players: usize) -> bool {
+    game_state.lobby.len() >= min_players
+        && game_state.lobby.keys().any(|client_id| !is_bot(*client_id))
+        && game_state.lobby.values().all(|entry| entry.ready)
+}
+
+/// Brings everyone in the lobby into a new match, played with the host's
+/// settings. Teams are dealt out afresh since the mode may have changed.
+pub fn start_match(game_state: &mut GameState, rng: &mut MazeRng) {
+    rng.0 = game_state
+        .seed
+        .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+
+    let mut members: Vec<u64> = game_state.lobby.keys().copied().collect();
+    members.sort();
+    game_state.players = members
+        .iter()
+        .map(|client_id| (*client_id, PlayerState::default()))
+        .collect();
+    game_state.scores = members
+        .iter()
+        .map(|client_id| (*client_id, PlayerScore::default()))
+        .collect();
+    game_state.teams.clear();
+    if game_state.mode.has_teams() {
+        for client_id in members {
+            let team = assign_team(&game_state.teams);
+            game_state.teams.insert(client_id, team);
+        }
+    }
+    if game_state.map == MapChoice::Fixed {
+        game_state.maze_seed = rng.0.gen();
+    }
+}
+
+/// Seed of the next round's maze.
+pub fn next_maze_seed(game_state: &GameState, rng: &mut MazeRng) -> u64 {
+    match game_state.map {
+        MapChoice::Shuffled => rng.0.gen(),
+        MapChoice::Fixed => game_state.maze_seed,
+    }
+}
+
+/// Ends the match, everyone waits in the lobby until they ready up again.
+/// The scores stay up until the next match starts.
+pub fn return_to_lobby(game_state: &mut GameState) {
+    game_state.phase = MatchPhase::Lobby;
+    game_state.players.clear();
+    game_state.alive.clear();
+    game_state.bullets.clear();
+    game_state.pickups.clear();
+    game_state.deployables.clear();
+    game_state.flags.clear();
+    for (client_id, entry) in game_state.lobby.iter_mut() {
+        entry.ready = is_bot(*client_id);
+    }
+}
@@ -1,3 +1,13 @@
+mod ammo;
+mod bots;
+mod deployables;
+mod lobby;
+mod modes;
+mod pickups;
+mod rounds;
+mod scoring;
+
+use ammo::{AmmoLedger, AmmoRules, BulletIds, WeaponCooldowns};
 use bevy::prelude::*;
 use bevy_renet::{
     netcode::{NetcodeServerPlugin, NetcodeServerTransport, ServerAuthentication, ServerConfig},
@@ -5,43 +15,80 @@ use bevy_renet::{
     RenetServerPlugin,
 };
 use bincode;
-use serde::{Deserialize, Serialize};
+use bots::{fill_host_bots_system, HostBotRules};
+use deployables::{expire_deployables_system, DeployableIds, DeployableRules};
+use lobby::MazeRng;
+use pickups::{spawn_pickups_system, PickupRules, PickupSpawner};
+use rounds::{in_round, round_flow_system, RoundRules, RoundTracker};
+use shared::{
+    lobby::MapChoice,
+    modes::GameMode,
+    protocol::{ClientMessage, GameState, PlayerState},
+    weapons::BulletState,
+};
 use std::{net::UdpSocket, time::SystemTime};
 
-#[derive(Component, Default, Debug, Clone, Serialize, Deserialize)]
-struct PlayerState {
-    position: Vec2,
-    rotation: f32,
-}
-
-#[derive(Component, Debug, Clone, Serialize, Deserialize)]
-struct BulletState {
-    position: Vec2,
-    angle: f32,
-}
-
-#[derive(Resource, Default, Serialize, Deserialize)]
-struct GameState {
-    player_blue: PlayerState,
-    player_red: PlayerState,
-    //bullets: Vec<BulletState>,
-}
-
 fn main() {
+    // `server [ffa|tdm|ctf] [--friendly-fire] [--no-host-bots] [--fixed-map] [--seed N]`,
+    // the host can change all but the bots from the lobby. Bots need a host to
+    // play them, see `bots::HostBotRules`
+    let mode = std::env::args()
+        .nth(1)
+        .and_then(|arg| GameMode::from_arg(&arg))
+        .unwrap_or_default();
+    let friendly_fire = std::env::args().any(|arg| arg == "--friendly-fire");
+    let map = if std::env::args().any(|arg| arg == "--fixed-map") {
+        MapChoice::Fixed
+    } else {
+        MapChoice::Shuffled
+    };
+    let seed = std::env::args()
+        .skip_while(|arg| arg != "--seed")
+        .nth(1)
+        .and_then(|seed| seed.parse().ok());
+    let bot_rules = if std::env::args().any(|arg| arg == "--no-host-bots") {
+        HostBotRules { fill_to: 0 }
+    } else {
+        HostBotRules::default()
+    };
+
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(RenetServerPlugin)
         .add_plugins(NetcodeServerPlugin)
         .insert_resource(new_server())
         .insert_resource(new_transport())
-        .insert_resource(GameState::default())
+        .insert_resource(GameState {
+            mode,
+            friendly_fire,
+            map,
+            seed,
+            ..default()
+        })
+        .init_resource::<AmmoRules>()
+        .insert_resource(WeaponCooldowns::load())
+        .init_resource::<AmmoLedger>()
+        .init_resource::<BulletIds>()
+        .init_resource::<PickupRules>()
+        .init_resource::<PickupSpawner>()
+        .init_resource::<DeployableRules>()
+        .init_resource::<DeployableIds>()
+        .init_resource::<RoundRules>()
+        .init_resource::<RoundTracker>()
+        .init_resource::<MazeRng>()
+        .insert_resource(bot_rules)
         .add_systems(
             Update,
             (
                 handle_events_system,
                 receive_message_system,
+                fill_host_bots_system,
+                round_flow_system,
+                spawn_pickups_system.run_if(in_round),
+                expire_deployables_system,
                 broadcast_state_system,
-            ),
+            )
+                .chain(),
         )
         .run();
 }
@@ -72,41 +119,136 @@ fn new_transport() -> NetcodeServerTransport {
 fn handle_events_system(
     mut server_events: EventReader<ServerEvent>,
     mut game_state: ResMut<GameState>,
+    mut ammo_ledger: ResMut<AmmoLedger>,
 ) {
     for event in server_events.read() {
         match event {
+            // Newcomers wait in the lobby, and join the next match once everyone is ready
             ServerEvent::ClientConnected { client_id } => {
                 println!("Client {} connected", client_id);
-                game_state.players.insert(
-                    *client_id,
-                    PlayerState {
-                        position: Vec2::ZERO,
-                        rotation: 0.0,
-                    },
-                );
+                lobby::join(&mut game_state, *client_id, format!("Player {client_id}"));
             }
             ServerEvent::ClientDisconnected { client_id, reason } => {
                 println!("Client {} disconnected: {:?}", client_id, reason);
-                game_state.players.remove(client_id);
+                lobby::leave(&mut game_state, *client_id);
+                ammo_ledger.0.remove(client_id);
             }
         }
     }
 }
 
 // Receive movement and shooting actions from clients
-fn receive_message_system(mut server: ResMut<RenetServer>, mut game_state: ResMut<GameState>) {
+fn receive_message_system(
+    mut server: ResMut<RenetServer>,
+    mut game_state: ResMut<GameState>,
+    (mut ammo_ledger, mut bullet_ids): (ResMut<AmmoLedger>, ResMut<BulletIds>),
+    mut deployable_ids: ResMut<DeployableIds>,
+    (ammo_rules, cooldowns, deployable_rules): (
+        Res<AmmoRules>,
+        Res<WeaponCooldowns>,
+        Res<DeployableRules>,
+    ),
+    time: Res<Time>,
+) {
     for client_id in server.clients_id() {
         while let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered)
         {
-            if let Ok(update) = bincode::deserialize::<PlayerState>(&message) {
-                if let Some(player) = game_state.players.get_mut(&client_id) {
-                    println!(
-                        "Received movement from {}: {:?}",
-                        client_id, update.position
-                    );
-                    player.position = update.position;
-                    player.rotation = update.rotation;
+            match bincode::deserialize::<ClientMessage>(&message) {
+                Ok(ClientMessage::Movement(player, update)) => {
+                    if !bots::drives(&game_state, client_id, player) {
+                        continue;
+                    }
+                    if let Some(player) = game_state.players.get_mut(&player) {
+                        player.linvel = update.linvel;
+                        player.position = update.position;
+                        player.rotation = update.rotation;
+                    }
+                }
+                Ok(ClientMessage::Fire(shot)) => {
+                    let Some((owner, weapon)) =
+                        shot.first().map(|bullet| (bullet.owner, bullet.weapon))
+                    else {
+                        continue;
+                    };
+                    if shot
+                        .iter()
+                        .any(|bullet| bullet.owner != owner || bullet.weapon != weapon)
+                        || !bots::drives(&game_state, client_id, owner)
+                        || !game_state.players.contains_key(&owner)
+                    {
+                        continue;
+                    }
+                    let live_bullets = ammo::live_bullets(&game_state, owner);
+                    let ammo = ammo_ledger.0.entry(owner).or_default();
+                    let cooldown = cooldowns.get(weapon);
+                    let now = time.elapsed_secs();
+                    if ammo_rules.try_fire(ammo, live_bullets, shot.len(), cooldown, now) {
+                        for bullet in shot {
+                            let id = bullet_ids.next();
+                            game_state.bullets.push(BulletState { id, ..bullet });
+                        }
+                    }
+                }
+                Ok(ClientMessage::CollectPickup(player, id)) => {
+                    if !bots::drives(&game_state, client_id, player) {
+                        continue;
+                    }
+                    pickups::collect(&mut game_state, id, player);
+                }
+                Ok(ClientMessage::Deploy(deployable)) => {
+                    if !bots::drives(&game_state, client_id, deployable.owner)
+                        || !game_state.players.contains_key(&deployable.owner)
+                    {
+                        continue;
+                    }
+                    let now = time.elapsed_secs();
+                    let id = deployable_ids.next();
+                    deployable_rules.try_deploy(&mut game_state, deployable, id, now);
+                }
+                Ok(ClientMessage::Detonate(id)) => {
+                    deployables::detonate(&mut game_state, id);
+                }
+                Ok(ClientMessage::Destroyed(player, killer)) => {
+                    if !bots::drives(&game_state, client_id, player) {
+                        continue;
+                    }
+                    if killer.is_some_and(|killer| !modes::can_damage(&game_state, killer, player))
+                    {
+                        continue;
+                    }
+                    // Only someone playing this match can take the kill
+                    let killer = killer.filter(|killer| game_state.players.contains_key(killer));
+                    if rounds::destroyed(&mut game_state, player) {
+                        scoring::record_kill(&mut game_state, player, killer);
+                    }
+                }
+                Ok(ClientMessage::Lobby(request)) => {
+                    lobby::handle_request(&mut game_state, client_id, request);
+                }
+                Ok(ClientMessage::BulletExpired(id)) => {
+                    let owner = game_state
+                        .bullets
+                        .iter()
+                        .find(|bullet| bullet.id == id)
+                        .map(|bullet| bullet.owner);
+                    if owner.is_some_and(|owner| bots::drives(&game_state, client_id, owner)) {
+                        game_state.bullets.retain(|bullet| bullet.id != id);
+                    }
+                }
+                Ok(ClientMessage::Laser(beam)) => {
+                    if !bots::drives(&game_state, client_id, beam.owner)
+                        || !game_state.players.contains_key(&beam.owner)
+                    {
+                        continue;
+                    }
+                    let live_bullets = ammo::live_bullets(&game_state, beam.owner);
+                    let ammo = ammo_ledger.0.entry(beam.owner).or_default();
+                    let cooldown = cooldowns.get(beam.weapon);
+                    if ammo_rules.try_fire(ammo, live_bullets, 1, cooldown, time.elapsed_secs()) {
+                        ammo::fire_beam(&mut game_state, beam);
+                    }
                 }
+                Err(_) => {}
             }
         }
     }
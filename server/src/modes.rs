@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use shared::{
+    grid::cell_center,
+    modes::{FlagState, GameMode, Team},
+};
+
+use crate::GameState;
+
+/// How close a player has to get to a flag to take it, as on the client.
+const FLAG_PICKUP_RADIUS: f32 = 30.;
+
+/// Team for a newly connected client: whichever has fewer players.
+pub fn assign_team(teams: &HashMap<u64, Team>) -> Team {
+    let members = |team: Team| teams.values().filter(|member| **member == team).count();
+    Team::ALL
+        .into_iter()
+        .min_by_key(|team| members(*team))
+        .unwrap_or(Team::Red)
+}
+
+/// Whether `source`'s shots may hurt `target`: anyone but a teammate unless
+/// friendly fire is on. Mirrors `ModeRules::can_damage` on the client.
+pub fn can_damage(game_state: &GameState, source: u64, target: u64) -> bool {
+    let source_team = game_state.teams.get(&source);
+    game_state.friendly_fire
+        || source == target
+        || source_team.is_none()
+        || source_team != game_state.teams.get(&target)
+}
+
+/// Flags back home, for the start of a round.
+pub fn reset_flags(game_state: &mut GameState) {
+    game_state.flags = if game_state.mode == GameMode::CaptureTheFlag {
+        Team::ALL
+            .into_iter()
+            .map(|team| FlagState {
+                team,
+                carrier: None,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+}
+
+/// Moves flags between bases and carriers. Returns the team that captured
+/// one, if any.
+pub fn update_flags(game_state: &mut GameState) -> Option<Team> {
+    let GameState {
+        flags,
+        players,
+        teams,
+        alive,
+        ..
+    } = game_state;
+
+    for flag in flags.iter_mut() {
+        match flag.carrier {
+            // A carrier who was destroyed or left drops the flag back home
+            Some(carrier) if !alive.contains(&carrier) => flag.carrier = None,
+            Some(_) => {}
+            None => {
+                let (x, y) = flag.team.base_cell();
+                let home = cell_center(x, y);
+                flag.carrier = players
+                    .iter()
+                    .filter(|(client_id, _)| alive.contains(client_id))
+                    .filter(|(client_id, _)| {
+                        teams.get(client_id).is_some_and(|team| *team != flag.team)
+                    })
+                    .find(|(_, player)| player.position.distance(home) <= FLAG_PICKUP_RADIUS)
+                    .map(|(client_id, _)| *client_id);
+            }
+        }
+    }
+
+    flags.iter().find_map(|flag| {
+        let carrier = flag.carrier?;
+        let team = *teams.get(&carrier)?;
+        let own_flag_home = flags
+            .iter()
+            .any(|own| own.team == team && own.carrier.is_none());
+        let position = players.get(&carrier)?.position;
+        (own_flag_home && team.in_base(position)).then_some(team)
+    })
+}
+
+/// Clients who take the round, or `None` while it is still being played.
+/// An empty list means nobody was left standing.
+pub fn round_winners(game_state: &GameState, captured: Option<Team>) -> Option<Vec<u64>> {
+    let alive = &game_state.alive;
+    let members = |team: Team| {
+        game_state
+            .teams
+            .iter()
+            .filter(|(_, member)| **member == team)
+            .map(|(client_id, _)| *client_id)
+            .collect::<Vec<_>>()
+    };
+    if let Some(team) = captured {
+        return Some(members(team));
+    }
+
+    if !game_state.mode.has_teams() {
+        return (alive.len() <= 1).then(|| alive.iter().copied().collect());
+    }
+    let standing: Vec<Team> = Team::ALL
+        .into_iter()
+        .filter(|team| {
+            alive
+                .iter()
+                .any(|client_id| game_state.teams.get(client_id) == Some(team))
+        })
+        .collect();
+    match standing.as_slice() {
+        [] => Some(Vec::new()),
+        [team] => Some(members(*team)),
+        _ => None,
+    }
+}
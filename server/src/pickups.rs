@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+use shared::{
+    grid::{
+        cell_at, cell_center, GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_SIZE,
+        GRID_CELL_VERTICAL_AMOUNT,
+    },
+    pickups::{CollectRecord, PickupKind, PickupState},
+};
+
+use crate::GameState;
+
+/// Pickups taken kept in the game state, for clients that missed a state.
+const COLLECTED_LENGTH: usize = 16;
+
+/// Server copy of the client's `PickupRules`.
+#[derive(Resource, Debug, Clone)]
+pub struct PickupRules {
+    /// Seconds between two spawns
+    pub spawn_interval: f32,
+    pub max_pickups: usize,
+}
+
+impl Default for PickupRules {
+    fn default() -> Self {
+        Self {
+            spawn_interval: 8.,
+            max_pickups: 3,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct PickupSpawner {
+    timer: Timer,
+    next_id: u32,
+}
+
+impl Default for PickupSpawner {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(PickupRules::default().spawn_interval, TimerMode::Repeating),
+            next_id: 0,
+        }
+    }
+}
+
+pub fn spawn_pickups_system(
+    time: Res<Time>,
+    rules: Res<PickupRules>,
+    mut spawner: ResMut<PickupSpawner>,
+    mut game_state: ResMut<GameState>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    if game_state.pickups.len() >= rules.max_pickups {
+        return;
+    }
+
+    let occupied: Vec<(i32, i32)> = game_state
+        .players
+        .values()
+        .map(|player| cell_at(player.position))
+        .chain(
+            game_state
+                .pickups
+                .iter()
+                .map(|pickup| (pickup.cell.0 as i32, pickup.cell.1 as i32)),
+        )
+        .collect();
+    let mut rng = rand::thread_rng();
+    let free_cell = (0..GRID_CELL_HORIZONTAL_AMOUNT)
+        .flat_map(|x| (0..GRID_CELL_VERTICAL_AMOUNT).map(move |y| (x, y)))
+        .filter(|&(x, y)| !occupied.contains(&(x as i32, y as i32)))
+        .choose(&mut rng);
+    let (Some(cell), Some(&kind)) = (free_cell, PickupKind::ALL.iter().choose(&mut rng)) else {
+        return;
+    };
+
+    game_state.pickups.push(PickupState {
+        id: spawner.next_id,
+        kind,
+        cell,
+    });
+    spawner.next_id += 1;
+}
+
+/// Gives pickup `id` to `player` if they are close enough to have driven
+/// over it. Returns whether it was taken.
+pub fn collect(game_state: &mut GameState, id: u32, player: u64) -> bool {
+    let Some(position) = game_state
+        .players
+        .get(&player)
+        .map(|player| player.position)
+    else {
+        return false;
+    };
+    let Some(index) = game_state.pickups.iter().position(|pickup| {
+        let center = cell_center(pickup.cell.0 as usize, pickup.cell.1 as usize);
+        pickup.id == id && center.distance(position) <= GRID_CELL_SIZE
+    }) else {
+        return false;
+    };
+    let pickup = game_state.pickups.swap_remove(index);
+
+    let id = game_state.collected.last().map_or(0, |taken| taken.id + 1);
+    game_state.collected.push(CollectRecord {
+        id,
+        player,
+        kind: pickup.kind,
+    });
+    if game_state.collected.len() > COLLECTED_LENGTH {
+        game_state.collected.remove(0);
+    }
+    true
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use shared::{
+    grid::{cell_center, GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_VERTICAL_AMOUNT},
+    protocol::MatchPhase,
+};
+
+use crate::{
+    lobby::{self, MazeRng},
+    modes, GameState, PlayerState,
+};
+
+/// Server copy of the client's `MatchRules`, plus how many players a match needs.
+#[derive(Resource, Debug, Clone)]
+pub struct RoundRules {
+    pub min_players: usize,
+    pub rounds_to_win: u32,
+    /// Seconds between the maze appearing and the round starting
+    pub countdown: f32,
+    /// Seconds the round result stays up before the next round
+    pub round_over: f32,
+    /// Seconds the final result stays up before going back to the lobby
+    pub match_over: f32,
+}
+
+impl Default for RoundRules {
+    fn default() -> Self {
+        Self {
+            min_players: 2,
+            rounds_to_win: 3,
+            countdown: 3.,
+            round_over: 3.,
+            match_over: 5.,
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct RoundTracker {
+    timer: Timer,
+}
+
+impl Default for RoundTracker {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(0., TimerMode::Once),
+        }
+    }
+}
+
+/// Takes a destroyed player out of the round. Returns whether it was still in it.
+pub fn destroyed(game_state: &mut GameState, client_id: u64) -> bool {
+    game_state.alive.remove(&client_id)
+}
+
+fn enter(phase: MatchPhase, seconds: f32, game_state: &mut GameState, tracker: &mut RoundTracker) {
+    game_state.phase = phase;
+    tracker.timer = Timer::from_seconds(seconds, TimerMode::Once);
+}
+
+/// Steps every client through the same rounds, clients follow `GameState::phase`.
+pub fn round_flow_system(
+    time: Res<Time>,
+    rules: Res<RoundRules>,
+    mut tracker: ResMut<RoundTracker>,
+    mut rng: ResMut<MazeRng>,
+    mut game_state: ResMut<GameState>,
+) {
+    let finished = tracker.timer.tick(time.delta()).finished();
+    let players = game_state.players.len();
+
+    if game_state.phase != MatchPhase::Lobby && players < rules.min_players {
+        lobby::return_to_lobby(&mut game_state);
+        return;
+    }
+
+    match game_state.phase {
+        MatchPhase::Lobby if lobby::all_ready(&game_state, rules.min_players) => {
+            lobby::start_match(&mut game_state, &mut rng);
+            start_round(&rules, &mut game_state, &mut tracker, &mut rng);
+        }
+        MatchPhase::Countdown if finished => {
+            game_state.phase = MatchPhase::InRound;
+        }
+        MatchPhase::InRound => {
+            let captured = modes::update_flags(&mut game_state);
+            let Some(winners) = modes::round_winners(&game_state, captured) else {
+                return;
+            };
+            for winner in winners {
+                game_state.scores.entry(winner).or_default().score += 1;
+            }
+            enter(
+                MatchPhase::RoundOver,
+                rules.round_over,
+                &mut game_state,
+                &mut tracker,
+            );
+        }
+        MatchPhase::RoundOver if finished => {
+            if game_state
+                .scores
+                .values()
+                .any(|score| score.score >= rules.rounds_to_win)
+            {
+                enter(
+                    MatchPhase::MatchOver,
+                    rules.match_over,
+                    &mut game_state,
+                    &mut tracker,
+                );
+            } else {
+                start_round(&rules, &mut game_state, &mut tracker, &mut rng);
+            }
+        }
+        MatchPhase::MatchOver if finished => {
+            lobby::return_to_lobby(&mut game_state);
+        }
+        _ => {}
+    }
+}
+
+/// Puts every player at their start for a new round, as `GameMode::spawn_point`
+/// does on the client: a corner each in free for all, by their team's base otherwise.
+pub fn place_players(game_state: &mut GameState) {
+    let mut players: Vec<u64> = game_state.players.keys().copied().collect();
+    players.sort();
+    let mut teammates = HashMap::new();
+    for (nth, client_id) in players.into_iter().enumerate() {
+        let (max_x, max_y) = (
+            GRID_CELL_HORIZONTAL_AMOUNT - 1,
+            GRID_CELL_VERTICAL_AMOUNT - 1,
+        );
+        let cell = match game_state.teams.get(&client_id) {
+            Some(team) => {
+                let count: &mut i32 = teammates.entry(*team).or_default();
+                // Teammates line up along the base column, alternating above and below it
+                let offset = if *count % 2 == 0 {
+                    *count / 2
+                } else {
+                    -(*count / 2 + 1)
+                };
+                *count += 1;
+                let (x, y) = team.base_cell();
+                (x as u32, (y as i32 + offset).clamp(0, max_y as i32) as u32)
+            }
+            None => [(0, max_y), (max_x, 0), (max_x, max_y), (0, 0)][nth % 4],
+        };
+        if let Some(player) = game_state.players.get_mut(&client_id) {
+            *player = PlayerState {
+                position: cell_center(cell.0 as usize, cell.1 as usize),
+                ..default()
+            };
+        }
+    }
+}
+
+/// Clears whatever the last round left behind, picks the next maze and
+/// puts everyone back at their start.
+fn start_round(
+    rules: &RoundRules,
+    game_state: &mut GameState,
+    tracker: &mut RoundTracker,
+    rng: &mut MazeRng,
+) {
+    game_state.bullets.clear();
+    game_state.pickups.clear();
+    game_state.deployables.clear();
+    game_state.maze_seed = lobby::next_maze_seed(game_state, rng);
+    modes::reset_flags(game_state);
+    place_players(game_state);
+    game_state.alive = game_state.players.keys().copied().collect();
+    enter(MatchPhase::Countdown, rules.countdown, game_state, tracker);
+}
+
+pub fn in_round(game_state: Res<GameState>) -> bool {
+    game_state.phase == MatchPhase::InRound
+}
@@ -0,0 +1,21 @@
+use shared::protocol::KillRecord;
+
+use crate::GameState;
+
+/// Kills kept in the game state for clients' kill feeds.
+const KILL_FEED_LENGTH: usize = 5;
+
+/// Books a death for `victim` and the kill for `killer`, as reported by
+/// whoever drives the victim.
+pub fn record_kill(game_state: &mut GameState, victim: u64, killer: Option<u64>) {
+    game_state.scores.entry(victim).or_default().deaths += 1;
+    if let Some(killer) = killer.filter(|killer| *killer != victim) {
+        game_state.scores.entry(killer).or_default().kills += 1;
+    }
+
+    let id = game_state.kill_feed.last().map_or(0, |kill| kill.id + 1);
+    game_state.kill_feed.push(KillRecord { id, killer, victim });
+    if game_state.kill_feed.len() > KILL_FEED_LENGTH {
+        game_state.kill_feed.remove(0);
+    }
+}
@@ -0,0 +1,31 @@
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeployableKind {
+    Mine,
+    Barrier,
+}
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WallType {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployableState {
+    pub kind: DeployableKind,
+    pub position: Vec2,
+    /// Orientation of a barrier, `None` for mines
+    pub wall_type: Option<WallType>,
+    /// Client id of the player placing it
+    pub owner: u64,
+    /// Handed out by the server once placed
+    pub id: u32,
+    /// Server time the deployable was placed, never sent to clients
+    #[serde(skip)]
+    pub placed_at: f32,
+}
@@ -0,0 +1,23 @@
+use bevy::math::Vec2;
+
+pub const GAME_FIELD_WIDTH: f32 = 720.0;
+pub const GAME_FIELD_HEIGHT: f32 = 720.0;
+pub const GRID_CELL_SIZE: f32 = 120.0;
+pub const GRID_CELL_HORIZONTAL_AMOUNT: u32 = (GAME_FIELD_WIDTH / GRID_CELL_SIZE) as u32;
+pub const GRID_CELL_VERTICAL_AMOUNT: u32 = (GAME_FIELD_HEIGHT / GRID_CELL_SIZE) as u32;
+
+pub fn cell_center(x: usize, y: usize) -> Vec2 {
+    Vec2::new(
+        -GAME_FIELD_WIDTH / 2.0 + GRID_CELL_SIZE * x as f32 + GRID_CELL_SIZE / 2.0,
+        -GAME_FIELD_HEIGHT / 2.0 + GRID_CELL_SIZE * y as f32 + GRID_CELL_SIZE / 2.0,
+    )
+}
+
+/// Maze cell containing `position`. Positions outside the field give
+/// coordinates outside the grid.
+pub fn cell_at(position: Vec2) -> (i32, i32) {
+    (
+        ((position.x + GAME_FIELD_WIDTH / 2.0) / GRID_CELL_SIZE).floor() as i32,
+        ((position.y + GAME_FIELD_HEIGHT / 2.0) / GRID_CELL_SIZE).floor() as i32,
+    )
+}
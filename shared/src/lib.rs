@@ -0,0 +1,11 @@
+//! What the client and the server have to agree on: the maze grid things
+//! are placed on and everything sent between them. bincode writes fields
+//! and variants by position, so both sides use these very types.
+
+pub mod deployables;
+pub mod grid;
+pub mod lobby;
+pub mod modes;
+pub mod pickups;
+pub mod protocol;
+pub mod weapons;
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::modes::GameMode;
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum TankClass {
+    Light,
+    #[default]
+    Medium,
+    Heavy,
+}
+
+impl TankClass {
+    pub const ALL: [TankClass; 3] = [TankClass::Light, TankClass::Medium, TankClass::Heavy];
+
+    /// The class's `*.class.ron` file, relative to the assets folder.
+    pub fn path(&self) -> &'static str {
+        match self {
+            TankClass::Light => "tanks/light.class.ron",
+            TankClass::Medium => "tanks/medium.class.ron",
+            TankClass::Heavy => "tanks/heavy.class.ron",
+        }
+    }
+}
+
+/// How well the host's client plays the bots.
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+}
+
+// Field order is part of the wire format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LobbyEntry {
+    pub name: String,
+    /// Index into the client's palette, no two players share one
+    pub color: u8,
+    pub class: TankClass,
+    pub ready: bool,
+    /// Orders everyone by when they joined, the server keeps it to itself
+    #[serde(skip)]
+    pub joined: u64,
+}
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapChoice {
+    /// A new maze every round
+    #[default]
+    Shuffled,
+    /// One maze for the whole match
+    Fixed,
+}
+
+/// What the host picks for the next match.
+// Field order is part of the wire format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchSettings {
+    pub mode: GameMode,
+    pub friendly_fire: bool,
+    pub map: MapChoice,
+    /// Seeds the match's mazes, `None` for different ones every match
+    pub seed: Option<u64>,
+    pub bots: Difficulty,
+}
+
+/// Something a client asks for while in the lobby.
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LobbyRequest {
+    Name(String),
+    Color(u8),
+    Class(TankClass),
+    Ready(bool),
+    /// Only taken from the host
+    Settings(MatchSettings),
+}
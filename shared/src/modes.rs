@@ -0,0 +1,88 @@
+use bevy::prelude::{Component, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{cell_at, GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_VERTICAL_AMOUNT};
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Every tank for itself, the last one standing takes the round
+    #[default]
+    FreeForAll,
+    /// Two teams, the last team standing takes the round
+    TeamDeathmatch,
+    /// Two teams, bringing the other team's flag home takes the round
+    CaptureTheFlag,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [
+        GameMode::FreeForAll,
+        GameMode::TeamDeathmatch,
+        GameMode::CaptureTheFlag,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::FreeForAll => "Free for all",
+            GameMode::TeamDeathmatch => "Team deathmatch",
+            GameMode::CaptureTheFlag => "Capture the flag",
+        }
+    }
+
+    /// Parses the mode named on the command line.
+    pub fn from_arg(arg: &str) -> Option<GameMode> {
+        match arg {
+            "ffa" => Some(GameMode::FreeForAll),
+            "tdm" => Some(GameMode::TeamDeathmatch),
+            "ctf" => Some(GameMode::CaptureTheFlag),
+            _ => None,
+        }
+    }
+
+    pub fn has_teams(&self) -> bool {
+        *self != GameMode::FreeForAll
+    }
+}
+
+// Variant order is part of the wire format.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Team {
+    Red,
+    Blue,
+}
+
+impl Team {
+    pub const ALL: [Team; 2] = [Team::Red, Team::Blue];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Team::Red => "Red",
+            Team::Blue => "Blue",
+        }
+    }
+
+    /// Maze cell the team spawns next to and keeps its flag in, on opposite
+    /// sides of the maze.
+    pub fn base_cell(&self) -> (usize, usize) {
+        match self {
+            Team::Red => (0, (GRID_CELL_VERTICAL_AMOUNT as usize - 1) / 2),
+            Team::Blue => (
+                GRID_CELL_HORIZONTAL_AMOUNT as usize - 1,
+                GRID_CELL_VERTICAL_AMOUNT as usize / 2,
+            ),
+        }
+    }
+
+    pub fn in_base(&self, position: Vec2) -> bool {
+        let (x, y) = self.base_cell();
+        cell_at(position) == (x as i32, y as i32)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagState {
+    pub team: Team,
+    /// Client carrying the flag, `None` while it is at home
+    pub carrier: Option<u64>,
+}
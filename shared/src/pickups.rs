@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PickupKind {
+    Shotgun,
+    Laser,
+    Frag,
+    Missile,
+    Shield,
+    SpeedBoost,
+    GhostBullets,
+    MineLayer,
+}
+
+impl PickupKind {
+    pub const ALL: [PickupKind; 8] = [
+        PickupKind::Shotgun,
+        PickupKind::Laser,
+        PickupKind::Frag,
+        PickupKind::Missile,
+        PickupKind::Shield,
+        PickupKind::SpeedBoost,
+        PickupKind::GhostBullets,
+        PickupKind::MineLayer,
+    ];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickupState {
+    pub id: u32,
+    pub kind: PickupKind,
+    /// Maze cell the pickup lies in
+    pub cell: (u32, u32),
+}
+
+/// A pickup the server let a player take, so whoever drives them applies it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectRecord {
+    /// Increases with every pickup taken so clients can tell which ones they have applied
+    pub id: u32,
+    pub player: u64,
+    pub kind: PickupKind,
+}
@@ -0,0 +1,110 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::{Resource, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deployables::DeployableState,
+    lobby::{Difficulty, LobbyEntry, LobbyRequest, MapChoice},
+    modes::{FlagState, GameMode, Team},
+    pickups::{CollectRecord, PickupState},
+    weapons::{BeamState, BulletState},
+};
+
+// Field order is part of the wire format.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub linvel: Vec2,
+    pub position: Vec2,
+    /// Hull rotation in degrees
+    pub rotation: f32,
+}
+
+// Field order is part of the wire format.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerScore {
+    /// Rounds won by being the last tank standing
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillRecord {
+    /// Increases with every kill so clients can tell which ones they have shown
+    pub id: u32,
+    pub killer: Option<u64>,
+    pub victim: u64,
+}
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchPhase {
+    #[default]
+    Lobby,
+    Countdown,
+    InRound,
+    RoundOver,
+    MatchOver,
+}
+
+/// What a client tells the server. Players are named by client id: the
+/// host's client also speaks for the bots, so anything about a tank says
+/// whose it is.
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Movement(u64, PlayerState),
+    /// Request to fire every projectile of one shot for `owner`. The server
+    /// drops it if they are out of ammo, otherwise the bullets show up in
+    /// `GameState::bullets`.
+    Fire(Vec<BulletState>),
+    /// This player drove over the pickup with this id. The server decides
+    /// who gets it in `GameState::collected`.
+    CollectPickup(u64, u32),
+    /// Dropped a mine or put down a barrier
+    Deploy(DeployableState),
+    /// The mine with this id went off
+    Detonate(u32),
+    /// This player's tank is out of the round, shot by the second player if any
+    Destroyed(u64, Option<u64>),
+    /// A pick made in the lobby
+    Lobby(LobbyRequest),
+    /// A bullet fired by a player the client drives is gone
+    BulletExpired(u32),
+    /// The beam's `owner` fired a ray weapon
+    Laser(BeamState),
+}
+
+/// Everything the server knows about the match, sent to every client.
+// Field order is part of the wire format.
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct GameState {
+    pub players: HashMap<u64, PlayerState>,
+    pub bullets: Vec<BulletState>,
+    pub pickups: Vec<PickupState>,
+    pub deployables: Vec<DeployableState>,
+    pub phase: MatchPhase,
+    /// Seed every client builds this round's maze from
+    pub maze_seed: u64,
+    pub scores: HashMap<u64, PlayerScore>,
+    pub kill_feed: Vec<KillRecord>,
+    pub mode: GameMode,
+    pub friendly_fire: bool,
+    pub teams: HashMap<u64, Team>,
+    pub flags: Vec<FlagState>,
+    /// Everyone connected, whether or not they are in the match
+    pub lobby: HashMap<u64, LobbyEntry>,
+    /// Client who picks the match settings
+    pub host: Option<u64>,
+    pub map: MapChoice,
+    /// Seeds the match's mazes, `None` for different ones every match
+    pub seed: Option<u64>,
+    /// Players still in the round
+    pub alive: HashSet<u64>,
+    pub bot_difficulty: Difficulty,
+    /// Latest pickups taken, oldest first
+    pub collected: Vec<CollectRecord>,
+    /// Latest beams fired, oldest first
+    pub beams: Vec<BeamState>,
+}
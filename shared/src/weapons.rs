@@ -0,0 +1,74 @@
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+// Variant order is part of the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WeaponKind {
+    Cannon,
+    Shotgun,
+    Laser,
+    Railgun,
+    Frag,
+    Missile,
+    /// What a frag round bursts into, no tank carries it
+    Shrapnel,
+}
+
+impl WeaponKind {
+    pub const ALL: [WeaponKind; 7] = [
+        WeaponKind::Cannon,
+        WeaponKind::Shotgun,
+        WeaponKind::Laser,
+        WeaponKind::Railgun,
+        WeaponKind::Frag,
+        WeaponKind::Missile,
+        WeaponKind::Shrapnel,
+    ];
+
+    /// The weapon to switch to after this one, skipping shrapnel.
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|kind| kind == self).unwrap_or(0);
+        match Self::ALL[(index + 1) % Self::ALL.len()] {
+            WeaponKind::Shrapnel => Self::ALL[0],
+            next => next,
+        }
+    }
+
+    /// The weapon's `*.weapon.ron` file, relative to the assets folder.
+    pub fn path(&self) -> &'static str {
+        match self {
+            WeaponKind::Cannon => "weapons/cannon.weapon.ron",
+            WeaponKind::Shotgun => "weapons/shotgun.weapon.ron",
+            WeaponKind::Laser => "weapons/laser.weapon.ron",
+            WeaponKind::Railgun => "weapons/railgun.weapon.ron",
+            WeaponKind::Frag => "weapons/frag.weapon.ron",
+            WeaponKind::Missile => "weapons/missile.weapon.ron",
+            WeaponKind::Shrapnel => "weapons/shrapnel.weapon.ron",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulletState {
+    pub position: Vec2,
+    pub angle: f32,
+    /// Client id of the player firing
+    pub owner: u64,
+    /// Handed out by the server once the shot is accepted
+    pub id: u32,
+    pub weapon: WeaponKind,
+    /// Flies through walls
+    pub ghost: bool,
+}
+
+/// A ray weapon shot, which hits straight away so every client traces it itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BeamState {
+    pub origin: Vec2,
+    /// Angle in degrees
+    pub angle: f32,
+    pub owner: u64,
+    pub weapon: WeaponKind,
+    /// Increases with every beam fired so clients can tell which ones they have traced
+    pub id: u32,
+}
@@ -9,6 +9,23 @@ pub struct Obb {
     pub rotation: f32,
 }
 
+/// Result of a narrow-phase test: `normal` points from `other` towards
+/// `self`, and moving `self` by `normal * depth` separates the two.
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub normal: Vec2,
+    pub depth: f32,
+    /// `self` touched one of `other`'s corners (e.g. a wall tip) rather than a face.
+    pub corner: bool,
+}
+
+/// Where a ray first enters a collider, `distance` is in units of the ray direction.
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub distance: f32,
+    pub normal: Vec2,
+}
+
 #[derive(Component, Debug, Clone)]
 pub enum Collider {
     Aabb(Aabb),
@@ -64,19 +81,11 @@ fn aabb_x_obb(aabb: Collider, obb: Collider) -> bool {
         // Calculate the distance between projections along this axis
         let distance = translation_vec.dot(axis).abs();
 
-        // Debugging print statements for tracking the values
-        println!(
-            "Axis: {:?}, Translation Vec: {:?}, Distance: {}, AABB Radius: {}, OBB Radius: {}",
-            axis, translation_vec, distance, aabb_radius, obb_radius
-        );
-
         // If there is a separating axis (distance is greater than sum of radii), return false
         if distance > aabb_radius + obb_radius {
             return false;
         }
     }
-    println!("Collision detected with wall {:?}", aabb);
-
     // If no separating axis was found, return true (collision detected)
     true
 }
@@ -97,11 +106,133 @@ fn aabb_x_aabb(aabb_1: Collider, aabb_2: Collider) -> bool {
     min.x <= other_max.x && max.x >= other_min.x && min.y <= other_max.y && max.y >= other_min.y
 }
 
+fn aabb_contact(aabb_1: &Aabb, aabb_2: &Aabb) -> Option<Contact> {
+    let delta = aabb_1.center.xy() - aabb_2.center.xy();
+    let overlap = aabb_1.half_extents.xy() + aabb_2.half_extents.xy() - delta.abs();
+    if overlap.x <= 0.0 || overlap.y <= 0.0 {
+        return None;
+    }
+
+    // How far the center of the first box sticks out past each face of the second.
+    let outside = delta.abs() - aabb_2.half_extents.xy();
+    if outside.x > 0.0 && outside.y > 0.0 {
+        // Past both faces: we are touching a corner, so push away from it diagonally.
+        return Some(Contact {
+            normal: (outside * delta.signum()).normalize(),
+            depth: overlap.min_element(),
+            corner: true,
+        });
+    }
+
+    let contact = if overlap.x < overlap.y {
+        Contact {
+            normal: Vec2::new(delta.x.signum(), 0.0),
+            depth: overlap.x,
+            corner: false,
+        }
+    } else {
+        Contact {
+            normal: Vec2::new(0.0, delta.y.signum()),
+            depth: overlap.y,
+            corner: false,
+        }
+    };
+    Some(contact)
+}
+
+fn obb_contact(obb_1: &Obb, obb_2: &Obb) -> Option<Contact> {
+    let delta = obb_1.center - obb_2.center;
+    let axes_1 = get_axes(obb_1.rotation);
+    let axes_2 = get_axes(obb_2.rotation);
+
+    let mut best: Option<Contact> = None;
+    for &axis in axes_1.iter().chain(&axes_2) {
+        let radius_1 = get_obb_projection_radius(axis, obb_1.half_extents, axes_1[0], axes_1[1]);
+        let radius_2 = get_obb_projection_radius(axis, obb_2.half_extents, axes_2[0], axes_2[1]);
+        let distance = delta.dot(axis);
+        let overlap = radius_1 + radius_2 - distance.abs();
+
+        // Separating axis found
+        if overlap <= 0.0 {
+            return None;
+        }
+        if best.is_none_or(|contact| overlap < contact.depth) {
+            let sign = if distance < 0.0 { -1.0 } else { 1.0 };
+            best = Some(Contact {
+                normal: axis * sign,
+                depth: overlap,
+                corner: false,
+            });
+        }
+    }
+    best
+}
+
 impl Collider {
+    pub fn contact(&self, other: &Self) -> Option<Contact> {
+        match (self, other) {
+            (Collider::Aabb(aabb_1), Collider::Aabb(aabb_2)) => aabb_contact(aabb_1, aabb_2),
+            _ => obb_contact(&self.as_obb(), &other.as_obb()),
+        }
+    }
+
+    /// Any collider as an oriented box; an AABB is just an OBB with no rotation.
+    pub fn as_obb(&self) -> Obb {
+        match self {
+            Collider::Aabb(aabb) => Obb {
+                center: aabb.center.xy(),
+                half_extents: aabb.half_extents.xy(),
+                rotation: 0.0,
+            },
+            Collider::Obb(obb) => obb.clone(),
+        }
+    }
+
+    /// Casts a ray against the collider grown by `margin` on every side, so a
+    /// box with half extents `margin` can be swept as if it were a point.
+    /// Rays starting inside the collider don't hit it.
+    pub fn ray_cast(&self, origin: Vec2, direction: Vec2, margin: Vec2) -> Option<RayHit> {
+        let obb = self.as_obb();
+        let axes = get_axes(obb.rotation);
+        let half_extents = obb.half_extents + margin;
+        let offset = origin - obb.center;
+
+        let mut enter = f32::NEG_INFINITY;
+        let mut exit = f32::INFINITY;
+        let mut normal = Vec2::ZERO;
+        for (axis, half_extent) in axes.into_iter().zip([half_extents.x, half_extents.y]) {
+            let start = offset.dot(axis);
+            let speed = direction.dot(axis);
+            if speed.abs() < f32::EPSILON {
+                // Parallel to this slab, so it has to already be inside it
+                if start.abs() > half_extent {
+                    return None;
+                }
+                continue;
+            }
+
+            let t_1 = (-half_extent - start) / speed;
+            let t_2 = (half_extent - start) / speed;
+            if t_1.min(t_2) > enter {
+                enter = t_1.min(t_2);
+                normal = if speed > 0.0 { -axis } else { axis };
+            }
+            exit = exit.min(t_1.max(t_2));
+        }
+
+        if enter > exit || enter < 0.0 {
+            return None;
+        }
+        Some(RayHit {
+            distance: enter,
+            normal,
+        })
+    }
+
     pub fn collides_with(&self, other: &Self) -> bool {
         match (self, other) {
             (Collider::Aabb(_), Collider::Aabb(_)) => aabb_x_aabb(self.clone(), other.clone()),
-            (Collider::Obb(_), Collider::Obb(_)) => panic!("Obb-Obb collision not implemented"),
+            (Collider::Obb(_), Collider::Obb(_)) => self.contact(other).is_some(),
             (Collider::Aabb(_), Collider::Obb(_)) => aabb_x_obb(self.clone(), other.clone()),
             (Collider::Obb(_), Collider::Aabb(_)) => aabb_x_obb(other.clone(), self.clone()),
         }
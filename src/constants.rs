@@ -1,11 +1,17 @@
-pub const GAME_FIELD_WIDTH: f32 = 720.0;
-pub const GAME_FIELD_HEIGHT: f32 = 720.0;
-pub const GRID_CELL_SIZE: f32 = 120.0;
-pub const GRID_CELL_HORIZONTAL_AMOUNT: u32 = (GAME_FIELD_WIDTH / GRID_CELL_SIZE) as u32;
-pub const GRID_CELL_VERTICAL_AMOUNT: u32 = (GAME_FIELD_HEIGHT / GRID_CELL_SIZE) as u32;
+pub use shared::grid::{
+    GAME_FIELD_HEIGHT, GAME_FIELD_WIDTH, GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_SIZE,
+    GRID_CELL_VERTICAL_AMOUNT,
+};
 
 pub const SERVER_ADDR: &str = "127.0.0.1"; // Ensure this is a valid IPv4 address
-pub const BULLET_SPEED: f32 = 250.;
-pub const BULLET_SIZE: f32 = 5.;
-pub const BULLET_HALF_EXTENTS: (f32, f32, f32) = (2.5, 2.5, 0.);
 pub const BULLET_OFFSET: f32 = 20.;
+pub const BULLET_MAX_BOUNCES: u8 = 5;
+/// Seconds a bullet lives if it runs out of neither bounces nor targets.
+pub const BULLET_LIFETIME: f32 = 10.;
+/// Seconds over which a bullet fades out before its lifetime runs out.
+pub const BULLET_FADE_OUT: f32 = 1.;
+/// Seconds during which a bullet ignores the wall it just bounced off.
+pub const BULLET_WALL_COOLDOWN: f32 = 0.05;
+pub const MAX_LIVE_BULLETS: usize = 5;
+/// Seconds between two shots of the same tank.
+pub const FIRE_COOLDOWN: f32 = 0.2;
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+
+pub use shared::grid::{cell_at, cell_center};
+
+use crate::constants::{GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_VERTICAL_AMOUNT};
+
+/// How many cells around the queried one are searched for walls.
+const NEIGHBOUR_RADIUS: i32 = 1;
+
+/// Like `cell_at`, but positions outside the field give the nearest cell.
+pub fn clamped_cell_at(position: Vec2) -> (i32, i32) {
+    let (x, y) = cell_at(position);
+    (
+        x.clamp(0, GRID_CELL_HORIZONTAL_AMOUNT as i32 - 1),
+        y.clamp(0, GRID_CELL_VERTICAL_AMOUNT as i32 - 1),
+    )
+}
+
+/// Broadphase for wall collisions: every wall entity is bucketed under the
+/// maze cell it was placed for, so a query only has to look at the cells
+/// around a position instead of at every wall in the arena.
+#[derive(Resource)]
+pub struct WallGrid {
+    cells: Vec<Vec<Vec<Entity>>>,
+}
+
+impl Default for WallGrid {
+    fn default() -> Self {
+        Self {
+            cells: vec![
+                vec![Vec::new(); GRID_CELL_VERTICAL_AMOUNT as usize];
+                GRID_CELL_HORIZONTAL_AMOUNT as usize
+            ],
+        }
+    }
+}
+
+impl WallGrid {
+    pub fn insert(&mut self, x: usize, y: usize, wall: Entity) {
+        self.cells[x][y].push(wall);
+    }
+
+    /// Buckets `wall` under the cell containing `position`, or the nearest
+    /// one for positions outside the field.
+    pub fn insert_at(&mut self, position: Vec2, wall: Entity) {
+        let (x, y) = clamped_cell_at(position);
+        self.insert(x as usize, y as usize, wall);
+    }
+
+    pub fn remove(&mut self, wall: Entity) {
+        for column in self.cells.iter_mut() {
+            for cell in column.iter_mut() {
+                cell.retain(|entity| *entity != wall);
+            }
+        }
+    }
+
+    /// Walls in the cell containing `position` and the cells bordering it.
+    pub fn neighbours(&self, position: Vec2) -> impl Iterator<Item = Entity> + '_ {
+        let max_x = GRID_CELL_HORIZONTAL_AMOUNT as i32 - 1;
+        let max_y = GRID_CELL_VERTICAL_AMOUNT as i32 - 1;
+        // Clamp so bullets that slipped outside the field still see the border walls.
+        let (cx, cy) = clamped_cell_at(position);
+
+        let xs = (cx - NEIGHBOUR_RADIUS).max(0)..=(cx + NEIGHBOUR_RADIUS).min(max_x);
+        xs.flat_map(move |x| {
+            let ys = (cy - NEIGHBOUR_RADIUS).max(0)..=(cy + NEIGHBOUR_RADIUS).min(max_y);
+            ys.flat_map(move |y| self.cells[x as usize][y as usize].iter().copied())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbours_return_walls_from_adjacent_cells() {
+        let mut grid = WallGrid::default();
+        let own = Entity::from_raw(1);
+        let side = Entity::from_raw(2);
+        let diagonal = Entity::from_raw(3);
+        let two_away = Entity::from_raw(4);
+        grid.insert(2, 2, own);
+        grid.insert(3, 2, side);
+        grid.insert(1, 3, diagonal);
+        grid.insert(4, 2, two_away);
+
+        let found: Vec<Entity> = grid.neighbours(cell_center(2, 2)).collect();
+        assert!(found.contains(&own));
+        assert!(found.contains(&side));
+        assert!(found.contains(&diagonal));
+        assert!(!found.contains(&two_away));
+    }
+
+    #[test]
+    fn neighbours_outside_the_field_see_the_border_cells() {
+        let mut grid = WallGrid::default();
+        let corner = Entity::from_raw(1);
+        grid.insert(0, 0, corner);
+
+        let outside = cell_center(0, 0) - Vec2::splat(1000.0);
+        assert_eq!(grid.neighbours(outside).collect::<Vec<_>>(), vec![corner]);
+    }
+
+    #[test]
+    fn removed_walls_are_not_returned() {
+        let mut grid = WallGrid::default();
+        let wall = Entity::from_raw(1);
+        grid.insert_at(cell_center(3, 3), wall);
+        grid.remove(wall);
+
+        assert_eq!(grid.neighbours(cell_center(3, 3)).count(), 0);
+    }
+}
@@ -0,0 +1,595 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    time::Instant,
+};
+
+use bevy::{app::PluginsState, prelude::*, tasks::tick_global_task_pools_on_main_thread};
+use bevy_rapier2d::prelude::Velocity;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::{
+        GAME_FIELD_HEIGHT, GAME_FIELD_WIDTH, GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_VERTICAL_AMOUNT,
+    },
+    plugins::{
+        bots::Difficulty,
+        classes::TankClass,
+        combat::{Health, TankDestroyed},
+        game_flow::{AppState, MatchRules, MatchScore, MazeRng},
+        input::{Controls, TankInput},
+        modes::Team,
+        pickups::PickupRules,
+        players::{LocalPlayers, Player, PlayerSlot, MAX_LOCAL_PLAYERS},
+        shooting::Bullet,
+        tank::Tank,
+    },
+    simulation::{classes_loaded, headless_app, parse_bot, parse_class, parse_or, LOAD_TIMEOUT},
+    walls::{Direction, Maze},
+};
+
+/// Frames each `step` plays unless told otherwise, a fifteenth of a second.
+const DEFAULT_FRAME_SKIP: u32 = 4;
+/// Frames `reset` waits for the next round to start before giving up.
+const RESET_FRAMES: u32 = 600;
+/// Bullets in `Observation::features`, the nearest ones.
+pub const OBSERVED_BULLETS: usize = 16;
+const KILL_REWARD: f32 = 1.;
+const DEATH_REWARD: f32 = -1.;
+const WIN_REWARD: f32 = 1.;
+
+/// Who plays in the environment and how time moves on.
+#[derive(Debug, Clone)]
+pub struct EnvConfig {
+    /// Class of every tank driven through `step`, they are P1, P2 and so on
+    pub agents: Vec<TankClass>,
+    /// Bots the agents play against, numbered after the agents
+    pub opponents: Vec<(TankClass, Difficulty)>,
+    /// Seeds the mazes, the same seed and actions play out the same way
+    pub seed: u64,
+    /// Frames played by each `step`
+    pub frame_skip: u32,
+    /// Steps after which an episode is cut short, 0 for never
+    pub max_steps: u32,
+    pub pickups: bool,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            agents: vec![TankClass::Medium],
+            opponents: vec![(TankClass::Medium, Difficulty::Normal)],
+            seed: 0,
+            frame_skip: DEFAULT_FRAME_SKIP,
+            max_steps: 1800,
+            pickups: false,
+        }
+    }
+}
+
+impl EnvConfig {
+    /// Reads `--gym [port] [--seed N] [--agents class,...]
+    /// [--bots class:difficulty,...] [--frame-skip N] [--max-steps N]
+    /// [--pickups]` from the command line, with the port to listen on.
+    /// `None` when the game should open its window as usual.
+    pub fn from_args() -> Option<(Self, u16)> {
+        let args: Vec<String> = std::env::args().collect();
+        let start = args.iter().position(|arg| arg == "--gym")?;
+        let mut config = Self::default();
+        let mut port = 5555;
+
+        let mut args = args[start + 1..].iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => config.seed = parse_or(arg, args.next(), config.seed),
+                "--frame-skip" => config.frame_skip = parse_or(arg, args.next(), config.frame_skip),
+                "--max-steps" => config.max_steps = parse_or(arg, args.next(), config.max_steps),
+                "--pickups" => config.pickups = true,
+                "--agents" => {
+                    let agents: Option<Vec<_>> = args
+                        .next()
+                        .map(|names| names.split(',').map(parse_class).collect())
+                        .unwrap_or_default();
+                    match agents {
+                        Some(agents) if !agents.is_empty() => config.agents = agents,
+                        _ => eprintln!("ignoring --agents: expected a list of classes"),
+                    }
+                }
+                "--bots" => {
+                    let bots: Option<Vec<_>> = args
+                        .next()
+                        .map(|specs| specs.split(',').map(parse_bot).collect())
+                        .unwrap_or_default();
+                    match bots {
+                        Some(bots) => config.opponents = bots,
+                        None => eprintln!("ignoring --bots: expected class:difficulty"),
+                    }
+                }
+                other => match other.parse() {
+                    Ok(other) => port = other,
+                    Err(_) => eprintln!("ignoring unknown argument {other}"),
+                },
+            }
+        }
+        Some((config, port))
+    }
+}
+
+/// What an agent does over one step.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentAction {
+    /// -1 full reverse to 1 full ahead
+    pub throttle: f32,
+    /// -1 hard right to 1 hard left
+    pub steer: f32,
+    /// Pulls the trigger once, at the start of the step
+    pub fire: bool,
+    /// World point the turret should face, in turret aiming
+    pub aim: Option<Vec2>,
+}
+
+impl AgentAction {
+    fn input(&self, first_frame: bool) -> TankInput {
+        TankInput {
+            throttle: self.throttle.clamp(-1., 1.),
+            steer: self.steer.clamp(-1., 1.),
+            fire: self.fire && first_frame,
+            aim: self.aim,
+            ..default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TankObservation {
+    /// Index of the player driving it, agents come first
+    pub player: usize,
+    pub position: Vec2,
+    /// Hull angle in degrees
+    pub rotation: f32,
+    pub velocity: Vec2,
+    /// Fraction of full health left
+    pub health: f32,
+    pub team: Option<Team>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulletObservation {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    /// Fired by the agent looking at it
+    pub own: bool,
+}
+
+/// Everything one agent sees of the arena.
+#[derive(Debug, Clone, Serialize)]
+pub struct Observation {
+    /// `None` once the agent's tank is destroyed
+    pub tank: Option<TankObservation>,
+    /// Every other tank still in the round, by player index
+    pub others: Vec<TankObservation>,
+    /// Nearest to the agent first
+    pub bullets: Vec<BulletObservation>,
+    /// Walls around every cell as `maze[y][x]`, one bit per side in
+    /// `Direction::ALL` order, set where the way is shut
+    pub maze: Vec<Vec<u8>>,
+}
+
+/// Positions and velocities in half field widths, so the field spans -1 to 1.
+fn scaled(value: Vec2) -> Vec2 {
+    value / (Vec2::new(GAME_FIELD_WIDTH, GAME_FIELD_HEIGHT) / 2.)
+}
+
+fn tank_features(tank: Option<&TankObservation>, teammate: bool) -> [f32; 9] {
+    let Some(tank) = tank else {
+        return [0.; 9];
+    };
+    let position = scaled(tank.position);
+    let velocity = scaled(tank.velocity);
+    let (sin, cos) = tank.rotation.to_radians().sin_cos();
+    [
+        1.,
+        position.x,
+        position.y,
+        cos,
+        sin,
+        velocity.x,
+        velocity.y,
+        tank.health,
+        teammate as u8 as f32,
+    ]
+}
+
+impl Observation {
+    /// The observation as a fixed length input for a network: the agent's
+    /// tank, a slot for every other player, the `OBSERVED_BULLETS` nearest
+    /// bullets and the maze's walls. Missing tanks and bullets are zeros.
+    pub fn features(&self) -> Vec<f32> {
+        let team = self.tank.as_ref().and_then(|tank| tank.team);
+        let mut features = tank_features(self.tank.as_ref(), false).to_vec();
+        for slot in 0..MAX_LOCAL_PLAYERS - 1 {
+            let other = self.others.get(slot);
+            let teammate = team.is_some() && other.is_some_and(|other| other.team == team);
+            features.extend(tank_features(other, teammate));
+        }
+        for slot in 0..OBSERVED_BULLETS {
+            features.extend(match self.bullets.get(slot) {
+                Some(bullet) => {
+                    let position = scaled(bullet.position);
+                    let velocity = scaled(bullet.velocity);
+                    let own = bullet.own as u8 as f32;
+                    [1., position.x, position.y, velocity.x, velocity.y, own]
+                }
+                None => [0.; 6],
+            });
+        }
+        for walls in self.maze.iter().flatten() {
+            features.extend((0..Direction::ALL.len()).map(|bit| (walls >> bit & 1) as f32));
+        }
+        features
+    }
+}
+
+/// What came of one `step`, one entry per agent.
+#[derive(Debug, Clone, Serialize)]
+pub struct Step {
+    pub observations: Vec<Observation>,
+    pub rewards: Vec<f32>,
+    /// The episode is over, `reset` starts the next
+    pub done: bool,
+    /// `done` because `max_steps` ran out rather than the round ending
+    pub truncated: bool,
+}
+
+/// Tanks destroyed since the last step began.
+#[derive(Resource, Default)]
+struct EnvEvents(Vec<TankDestroyed>);
+
+fn record_events_system(
+    mut destroyed_events: EventReader<TankDestroyed>,
+    mut events: ResMut<EnvEvents>,
+) {
+    events.0.extend(destroyed_events.read().copied());
+}
+
+/// The real game, headless, with some tanks driven from outside it for
+/// training agents. Every round is an episode: `reset` starts one in a fresh
+/// maze, `step` plays `frame_skip` frames with the agents' actions.
+///
+/// Agents get `KILL_REWARD` for every tank they destroy, `DEATH_REWARD` when
+/// destroyed and `WIN_REWARD` for taking the round in free-for-all.
+pub struct TankEnv {
+    app: App,
+    config: EnvConfig,
+    steps: u32,
+}
+
+impl TankEnv {
+    pub fn new(config: EnvConfig) -> Result<Self, String> {
+        let players = config.agents.len() + config.opponents.len();
+        if config.agents.is_empty() || !(2..=MAX_LOCAL_PLAYERS).contains(&players) {
+            return Err(format!(
+                "expected at least one agent and 2 to {MAX_LOCAL_PLAYERS} tanks in all"
+            ));
+        }
+
+        let mut app = headless_app();
+        let slots = config
+            .agents
+            .iter()
+            .map(|class| PlayerSlot {
+                controls: Controls::Agent,
+                class: *class,
+            })
+            .chain(
+                config
+                    .opponents
+                    .iter()
+                    .map(|(class, difficulty)| PlayerSlot {
+                        controls: Controls::Bot(*difficulty),
+                        class: *class,
+                    }),
+            )
+            .collect();
+        app.insert_resource(LocalPlayers(slots))
+            .insert_resource(MatchRules {
+                // Episodes go on for as long as the agents are trained
+                rounds_to_win: u32::MAX,
+                countdown: 0.,
+                round_over: 0.,
+                driven_locally: true,
+            })
+            .insert_resource(MazeRng(StdRng::seed_from_u64(config.seed)))
+            .insert_resource(PickupRules {
+                spawn_locally: config.pickups,
+                ..default()
+            })
+            .init_resource::<EnvEvents>()
+            .add_systems(Update, record_events_system);
+
+        // What `App::run` would do before the first update
+        while app.plugins_state() == PluginsState::Adding {
+            tick_global_task_pools_on_main_thread();
+        }
+        app.finish();
+        app.cleanup();
+
+        Ok(Self {
+            app,
+            config,
+            steps: 0,
+        })
+    }
+
+    fn state(&self) -> AppState {
+        *self.app.world().resource::<State<AppState>>().get()
+    }
+
+    fn wait_for_classes(&mut self) -> Result<(), String> {
+        let started = Instant::now();
+        loop {
+            let world = self.app.world();
+            if classes_loaded(
+                world.resource(),
+                world.resource(),
+                world.resource(),
+                world.resource(),
+                world.resource(),
+            ) {
+                return Ok(());
+            }
+            if started.elapsed().as_secs_f32() > LOAD_TIMEOUT {
+                return Err(String::from(
+                    "tank classes or weapons did not load, is the assets folder next to the game?",
+                ));
+            }
+            self.app.update();
+        }
+    }
+
+    /// Ends whatever round is on and starts the next one in a fresh maze.
+    pub fn reset(&mut self) -> Result<Vec<Observation>, String> {
+        self.wait_for_classes()?;
+        let state = self.state();
+        let world = self.app.world_mut();
+        match state {
+            AppState::InRound => {
+                world.resource_mut::<MatchScore>().round_winner = None;
+                world
+                    .resource_mut::<NextState<AppState>>()
+                    .set(AppState::RoundOver);
+            }
+            // Already on the way to the next round
+            AppState::Countdown | AppState::RoundOver => {}
+            AppState::MainMenu | AppState::Lobby | AppState::MatchOver => {
+                world
+                    .resource_mut::<NextState<AppState>>()
+                    .set(AppState::Countdown);
+            }
+        }
+
+        for _ in 0..RESET_FRAMES {
+            self.app.update();
+            if self.state() == AppState::InRound {
+                self.steps = 0;
+                self.app.world_mut().resource_mut::<EnvEvents>().0.clear();
+                return Ok(self.observe());
+            }
+        }
+        Err(String::from("the next round never started"))
+    }
+
+    /// Plays `frame_skip` frames with every agent doing what `actions` says,
+    /// in player order. Agents without an action sit still.
+    pub fn step(&mut self, actions: &[AgentAction]) -> Step {
+        let agents = self.config.agents.len();
+        self.app.world_mut().resource_mut::<EnvEvents>().0.clear();
+
+        let mut round_over = self.state() != AppState::InRound;
+        for frame in 0..self.config.frame_skip.max(1) {
+            if round_over {
+                break;
+            }
+            let world = self.app.world_mut();
+            let mut input_query = world.query::<(&Player, &mut TankInput)>();
+            for (player, mut input) in input_query.iter_mut(world) {
+                if player.0 < agents {
+                    *input = actions
+                        .get(player.0)
+                        .map(|action| action.input(frame == 0))
+                        .unwrap_or_default();
+                }
+            }
+            self.app.update();
+            round_over = self.state() != AppState::InRound;
+        }
+
+        self.steps += 1;
+        let truncated =
+            !round_over && self.config.max_steps > 0 && self.steps >= self.config.max_steps;
+        Step {
+            observations: self.observe(),
+            rewards: self.rewards(round_over),
+            done: round_over || truncated,
+            truncated,
+        }
+    }
+
+    fn rewards(&self, round_over: bool) -> Vec<f32> {
+        let world = self.app.world();
+        let winner = round_over
+            .then(|| world.resource::<MatchScore>().round_winner.clone())
+            .flatten();
+        (0..self.config.agents.len())
+            .map(|index| {
+                let player = Player(index);
+                let mut reward = 0.;
+                for event in &world.resource::<EnvEvents>().0 {
+                    if event.victim_player == Some(player) {
+                        reward += DEATH_REWARD;
+                    } else if event.killer_player == Some(player) {
+                        reward += KILL_REWARD;
+                    }
+                }
+                if winner == Some(player.name()) {
+                    reward += WIN_REWARD;
+                }
+                reward
+            })
+            .collect()
+    }
+
+    /// What every agent sees right now, in player order.
+    pub fn observe(&mut self) -> Vec<Observation> {
+        let world = self.app.world_mut();
+        let mut tank_query = world
+            .query_filtered::<(&Player, &Transform, &Velocity, &Health, Option<&Team>), With<Tank>>(
+            );
+        let mut bullet_query = world.query::<(&Bullet, &Transform)>();
+        let world = &*world;
+
+        let mut tanks: Vec<TankObservation> = tank_query
+            .iter(world)
+            .map(
+                |(player, transform, velocity, health, team)| TankObservation {
+                    player: player.0,
+                    position: transform.translation.xy(),
+                    rotation: transform.rotation.to_euler(EulerRot::XYZ).2.to_degrees(),
+                    velocity: velocity.linvel,
+                    health: health.current / health.max,
+                    team: team.copied(),
+                },
+            )
+            .collect();
+        // Query order can change between steps, `features` needs each opponent
+        // in the same slot every time
+        tanks.sort_by_key(|tank| tank.player);
+        let bullets: Vec<(BulletObservation, Option<&Player>)> = bullet_query
+            .iter(world)
+            .map(|(bullet, transform)| {
+                let observation = BulletObservation {
+                    position: transform.translation.xy(),
+                    velocity: Vec2::from_angle(bullet.angle.to_radians()) * bullet.speed,
+                    own: false,
+                };
+                (observation, world.get::<Player>(bullet.owner))
+            })
+            .collect();
+        let maze = world.resource::<Maze>();
+        let walls: Vec<Vec<u8>> = (0..GRID_CELL_VERTICAL_AMOUNT as usize)
+            .map(|y| {
+                (0..GRID_CELL_HORIZONTAL_AMOUNT as usize)
+                    .map(|x| {
+                        Direction::ALL
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(_, direction)| !maze.is_open(x, y, *direction))
+                            .fold(0, |walls, (bit, _)| walls | 1 << bit)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        (0..self.config.agents.len())
+            .map(|index| {
+                let tank = tanks.iter().find(|tank| tank.player == index).cloned();
+                let mut bullets: Vec<BulletObservation> = bullets
+                    .iter()
+                    .map(|(bullet, owner)| BulletObservation {
+                        own: owner.is_some_and(|owner| owner.0 == index),
+                        ..bullet.clone()
+                    })
+                    .collect();
+                if let Some(tank) = &tank {
+                    bullets.sort_by(|a, b| {
+                        let a = a.position.distance_squared(tank.position);
+                        let b = b.position.distance_squared(tank.position);
+                        a.total_cmp(&b)
+                    });
+                }
+                Observation {
+                    others: tanks
+                        .iter()
+                        .filter(|other| other.player != index)
+                        .cloned()
+                        .collect(),
+                    tank,
+                    bullets,
+                    maze: walls.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A request to `serve`, one JSON value per line: `"reset"`, `"observe"`,
+/// `"features"` or `{"step": [{"throttle": 1, "steer": 0, "fire": true}]}`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Reset,
+    Observe,
+    /// `Observation::features` for every agent
+    Features,
+    Step(Vec<AgentAction>),
+}
+
+/// The answer to each `Request`, one JSON value per line.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Reply {
+    Observations(Vec<Observation>),
+    Features(Vec<Vec<f32>>),
+    Step(Step),
+    Error(String),
+}
+
+/// Answers requests from one client until it hangs up.
+fn serve_client(env: &mut TankEnv, stream: TcpStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str(&line) {
+            Ok(Request::Reset) => env.reset().map_or_else(Reply::Error, Reply::Observations),
+            Ok(Request::Observe) => Reply::Observations(env.observe()),
+            Ok(Request::Features) => {
+                Reply::Features(env.observe().iter().map(Observation::features).collect())
+            }
+            Ok(Request::Step(actions)) => Reply::Step(env.step(&actions)),
+            Err(err) => Reply::Error(err.to_string()),
+        };
+        let json = serde_json::to_string(&reply).map_err(io::Error::other)?;
+        writeln!(writer, "{json}")?;
+    }
+    Ok(())
+}
+
+/// Runs an environment behind a local TCP socket so agents written in any
+/// language can train against it, one client at a time.
+pub fn serve(config: EnvConfig, port: u16) {
+    let mut env = match TankEnv::new(config) {
+        Ok(env) => env,
+        Err(err) => {
+            eprintln!("could not start the environment: {err}");
+            return;
+        }
+    };
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("could not listen on port {port}: {err}");
+            return;
+        }
+    };
+    println!("environment listening on 127.0.0.1:{port}");
+    for stream in listener.incoming() {
+        if let Err(err) = stream.and_then(|stream| serve_client(&mut env, stream)) {
+            eprintln!("client dropped: {err}");
+        }
+    }
+}
@@ -1,13 +1,36 @@
 mod collider;
 mod constants;
+mod grid;
+mod gym;
 mod network_plugin;
 mod plugins;
+mod simulation;
 mod walls;
 use bevy::prelude::*;
 
 use bevy_rapier2d::plugin::{NoUserData, RapierPhysicsPlugin};
-use plugins::tank::{Tank, TankPlugin};
-use walls::setup_walls;
+use network_plugin::NetworkPlugin;
+use plugins::{
+    bindings::BindingsPlugin,
+    bots::BotsPlugin,
+    classes::ClassesPlugin,
+    collision::CollisionPlugin,
+    colors::ColorsPlugin,
+    combat::CombatPlugin,
+    debug::DebugOverlayPlugin,
+    deployables::DeployablesPlugin,
+    game_flow::GameFlowPlugin,
+    hud::HudPlugin,
+    input::InputPlugin,
+    modes::ModesPlugin,
+    pickups::PickupsPlugin,
+    players::PlayersPlugin,
+    scoring::ScoringPlugin,
+    shooting::BulletPlugin,
+    tank::{Tank, TankPlugin},
+    weapons::WeaponsPlugin,
+};
+use simulation::SimulationConfig;
 
 #[derive(Component, Default)]
 pub struct Velocity {
@@ -16,13 +39,42 @@ pub struct Velocity {
 }
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugins((TankPlugin,))
+    if let Some(config) = SimulationConfig::from_args() {
+        simulation::run(config);
+        return;
+    }
+    if let Some((config, port)) = gym::EnvConfig::from_args() {
+        gym::serve(config, port);
+        return;
+    }
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    add_game_plugins(&mut app);
+    // `--connect [ip[:port]]` plays on a server instead of on this machine
+    if let Some(server_addr) = network_plugin::server_addr_from_args() {
+        app.add_plugins(NetworkPlugin { server_addr });
+    }
+    app.add_systems(Startup, camera_setup).run();
+}
+
+/// Everything the game adds on top of the engine, shared by the window and
+/// the headless simulation.
+fn add_game_plugins(app: &mut App) {
+    app.add_plugins((TankPlugin, BulletPlugin, CollisionPlugin, CombatPlugin))
+        .add_plugins((ClassesPlugin, BindingsPlugin, InputPlugin, PlayersPlugin))
+        .add_plugins(BotsPlugin)
+        .add_plugins((WeaponsPlugin, PickupsPlugin, DeployablesPlugin))
+        .add_plugins((GameFlowPlugin, ModesPlugin, ScoringPlugin, ColorsPlugin))
+        .add_plugins((DebugOverlayPlugin, HudPlugin))
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
-        .add_systems(Startup, (camera_setup, setup_walls).chain())
-        //.add_systems(PostUpdate, movement_system)
-        .run();
+        .add_systems(PostUpdate, movement_system);
+}
+fn movement_system(time: Res<Time>, mut query: Query<(&Velocity, &mut Transform)>) {
+    for (velocity, mut transform) in query.iter_mut() {
+        transform.translation.x += velocity.x * time.delta_secs();
+        transform.translation.y += velocity.y * time.delta_secs();
+    }
 }
 fn camera_setup(mut commands: Commands) {
     commands.spawn(Camera2d::default());
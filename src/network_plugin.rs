@@ -1,57 +1,226 @@
 use std::{
-    net::{Ipv4Addr, UdpSocket},
-    time::SystemTime,
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr, UdpSocket},
+    time::{Duration, SystemTime},
 };
 
-use crate::constants::SERVER_ADDR;
+use crate::{
+    constants::SERVER_ADDR,
+    grid::{cell_center, WallGrid},
+    plugins::{
+        bindings::Bindings,
+        bots::{Bot, Difficulty},
+        classes::{TankClass, TankClassDef, TankClasses},
+        colors::{Palette, PlayerColor, PALETTE_SIZE},
+        combat::{Health, TankDestroyed},
+        deployables::{
+            spawn_barrier, spawn_mine, Barrier, DeployableKind, DeployableRules, Deployed, Mine,
+            MineDetonated,
+        },
+        game_flow::{AppState, LobbyRoster, MatchRules},
+        input::{Controls, TankInput},
+        modes::{Flag, GameMode, ModeRules, Team},
+        pickups::{
+            apply_pickup, spawn_pickup, Pickup, PickupCollected, PickupRules, PowerUp, PowerUps,
+            TemporaryWeapon,
+        },
+        players::{LocalPlayers, Player},
+        scoring::{KillFeed, Scoreboard},
+        shooting::{
+            spawn_bullet, AmmoRules, Bullet, BulletExpired, BulletFired, BulletLifetime, Gun,
+        },
+        tank::{spawn_tank, Remote, Tank},
+        weapons::{Armory, EquippedWeapon, LaserFired, WeaponDef},
+    },
+    walls::MazeSeed,
+};
 use bevy::prelude::*;
+use bevy_rapier2d::prelude::{KinematicCharacterController, RigidBody, Velocity};
 use bevy_renet::{
     netcode::{ClientAuthentication, NetcodeClientPlugin, NetcodeClientTransport},
     renet::{ConnectionConfig, DefaultChannel, RenetClient},
     RenetClientPlugin,
 };
 use bincode;
-use serde::{Deserialize, Serialize};
+use rand::Rng;
+use shared::{
+    deployables::DeployableState,
+    lobby::{LobbyRequest, MapChoice, MatchSettings},
+    protocol::{ClientMessage, GameState, MatchPhase, PlayerState},
+    weapons::{BeamState, BulletState},
+};
+
+/// Port the server listens on, unless `--connect` names another.
+const SERVER_PORT: u16 = 5000;
+/// Client ids from here up belong to the server's bots.
+const FIRST_BOT_ID: u64 = 1 << 48;
+
+impl From<MatchPhase> for AppState {
+    fn from(phase: MatchPhase) -> Self {
+        match phase {
+            MatchPhase::Lobby => AppState::Lobby,
+            MatchPhase::Countdown => AppState::Countdown,
+            MatchPhase::InRound => AppState::InRound,
+            MatchPhase::RoundOver => AppState::RoundOver,
+            MatchPhase::MatchOver => AppState::MatchOver,
+        }
+    }
+}
+
+/// What this player asked for on the command line: `--name NAME` to go by
+/// and, when hosting, `--seed N` for the match's mazes.
+#[derive(Resource, Debug, Default)]
+struct LobbyProfile {
+    name: Option<String>,
+    seed: Option<u64>,
+}
+
+impl LobbyProfile {
+    fn from_args() -> Self {
+        let value = |flag: &str| std::env::args().skip_while(|arg| arg != flag).nth(1);
+        Self {
+            name: value("--name"),
+            seed: value("--seed").and_then(|seed| seed.parse().ok()),
+        }
+    }
+}
+
+/// Where `--connect [ip[:port]]` asks to play, `None` without the flag.
+/// The address defaults to `SERVER_ADDR` and the port to `SERVER_PORT`.
+pub fn server_addr_from_args() -> Option<SocketAddr> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--connect");
+    args.next()?;
+    let addr = args
+        .next()
+        .filter(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| SERVER_ADDR.to_string());
+    let parsed = addr.parse().or_else(|_| {
+        addr.parse::<IpAddr>()
+            .map(|ip| SocketAddr::new(ip, SERVER_PORT))
+    });
+    match parsed {
+        Ok(server_addr) => Some(server_addr),
+        Err(_) => {
+            eprintln!("ignoring --connect {addr}: expected an address like 127.0.0.1:5000");
+            None
+        }
+    }
+}
 
-#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
-struct PlayerState {
-    linvel: Vec2,
-    position: Vec2,
-    rotation: f32,
+/// The id this client goes by on the server.
+#[derive(Resource, Debug, Clone, Copy)]
+struct LocalClient {
+    id: u64,
 }
 
-#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
-struct BulletState {
-    position: Vec2,
-    angle: f32,
+/// Server id of the player a tank plays for.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+struct NetworkId(u64);
+
+fn is_bot(client_id: u64) -> bool {
+    client_id >= FIRST_BOT_ID
 }
 
-#[derive(Resource, Serialize, Deserialize)]
-struct GameState {
-    players: std::collections::HashMap<u64, PlayerState>,
-    bullets: Vec<BulletState>,
+/// Bullets fired by players driven here, which this client reports once gone.
+#[derive(Resource, Debug, Default)]
+struct DrivenBullets(HashMap<Entity, u32>);
+
+/// Whether this client plays `player`: its own tank, and every bot when it
+/// is the host. Mirrors `bots::drives` on the server.
+fn drives(game_state: &GameState, client_id: u64, player: u64) -> bool {
+    player == client_id || (is_bot(player) && game_state.host == Some(client_id))
 }
 
-pub struct NetworkPlugin;
+pub struct NetworkPlugin {
+    pub server_addr: SocketAddr,
+}
 
 impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
+        let current_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        // Unsecure clients pick their own id. A random one keeps two apart
+        // and stays clear of the ids of the server's bots.
+        let local_client = LocalClient {
+            id: rand::thread_rng().gen_range(0..FIRST_BOT_ID),
+        };
+        let mut local_players = LocalPlayers::default();
+        local_players.0.truncate(1);
+
         app.add_plugins(RenetClientPlugin)
             .add_plugins(NetcodeClientPlugin)
             .insert_resource(new_client())
-            .insert_resource(new_transport())
+            .insert_resource(new_transport(
+                self.server_addr,
+                local_client.id,
+                current_time,
+            ))
+            .insert_resource(local_client)
+            .insert_resource(local_players)
             .insert_resource(GameState {
                 players: std::collections::HashMap::new(),
                 bullets: Vec::new(),
+                pickups: Vec::new(),
+                deployables: Vec::new(),
+                phase: MatchPhase::Lobby,
+                maze_seed: 0,
+                scores: std::collections::HashMap::new(),
+                kill_feed: Vec::new(),
+                mode: GameMode::default(),
+                friendly_fire: false,
+                teams: std::collections::HashMap::new(),
+                flags: Vec::new(),
+                lobby: std::collections::HashMap::new(),
+                host: None,
+                map: MapChoice::Shuffled,
+                seed: None,
+                alive: std::collections::HashSet::new(),
+                bot_difficulty: Difficulty::default(),
+                collected: Vec::new(),
+                beams: Vec::new(),
+            })
+            .insert_resource(LobbyProfile::from_args())
+            .insert_resource(MatchRules {
+                driven_locally: false,
+                ..default()
+            })
+            .insert_resource(PickupRules {
+                spawn_locally: false,
+                ..default()
+            })
+            .insert_resource(AmmoRules {
+                spawn_locally: false,
+                ..default()
             })
-            .insert_resource(PlayerState {
-                position: Vec2::ZERO,
-                linvel: Vec2::ZERO,
-                rotation: 0.0,
+            .insert_resource(DeployableRules {
+                spawn_locally: false,
             })
+            .init_resource::<DrivenBullets>()
             .add_systems(
                 PostUpdate,
-                (send_movement_system, receive_game_state_system),
+                (
+                    send_movement_system,
+                    send_fire_system,
+                    send_laser_system,
+                    send_expired_system,
+                    send_pickup_system,
+                    send_deploy_system,
+                    send_destroyed_system,
+                    send_lobby_system.run_if(in_state(AppState::Lobby)),
+                    receive_game_state_system,
+                    sync_phase_system,
+                    sync_players_system,
+                    sync_bullets_system,
+                    sync_deployables_system,
+                    sync_lobby_system,
+                    sync_scores_system,
+                    sync_mode_system,
+                    sync_pickups_system,
+                    sync_collected_system,
+                    sync_beams_system,
+                )
+                    .chain(),
             );
     }
 }
@@ -60,54 +229,124 @@ fn new_client() -> RenetClient {
     RenetClient::new(ConnectionConfig::default())
 }
 
-fn new_transport() -> NetcodeClientTransport {
-    let server_ip: Ipv4Addr = SERVER_ADDR.parse().expect("Invalid SERVER_ADDR");
-    let socket_addr = std::net::SocketAddr::new(server_ip.into(), 5000);
-
+fn new_transport(
+    server_addr: SocketAddr,
+    client_id: u64,
+    current_time: Duration,
+) -> NetcodeClientTransport {
     let authentication = ClientAuthentication::Unsecure {
-        server_addr: socket_addr,
-        client_id: 1,
+        server_addr,
+        client_id,
         user_data: None,
         protocol_id: 12345,
     };
 
-    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
-    let current_time = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap();
+    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
 
     NetcodeClientTransport::new(current_time, authentication, socket).unwrap()
 }
 
-// Send input data
+// Report where the tanks driven here are while they move, whether driven
+// or pushed aside by another tank
 fn send_movement_system(
     mut client: ResMut<RenetClient>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_state: ResMut<PlayerState>,
+    tank_query: Query<(&NetworkId, &TankInput, &Transform, &Velocity)>,
+    mut last_sent: Local<HashMap<u64, Vec2>>,
 ) {
-    let mut moved = false;
-
-    if keyboard_input.pressed(KeyCode::KeyW) {
-        player_state.position.y += 1.0;
-        moved = true;
-    }
-    if keyboard_input.pressed(KeyCode::KeyS) {
-        player_state.position.y -= 1.0;
-        moved = true;
+    for (id, input, transform, velocity) in tank_query.iter() {
+        let position = transform.translation.xy();
+        let idle = input.throttle == 0.0 && input.steer == 0.0;
+        if idle && last_sent.get(&id.0) == Some(&position) {
+            continue;
+        }
+        last_sent.insert(id.0, position);
+        let message = ClientMessage::Movement(
+            id.0,
+            PlayerState {
+                linvel: velocity.linvel,
+                position: transform.translation.xy(),
+                rotation: transform.rotation.to_euler(EulerRot::XYZ).2.to_degrees(),
+            },
+        );
+        let movement_data = bincode::serialize(&message).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, movement_data);
     }
-    if keyboard_input.pressed(KeyCode::KeyA) {
-        player_state.position.x -= 1.0;
-        moved = true;
+}
+
+// Ask the server to fire each shot, projectile by projectile
+fn send_fire_system(
+    mut client: ResMut<RenetClient>,
+    mut fired_events: EventReader<BulletFired>,
+    owner_query: Query<(&NetworkId, &PowerUps)>,
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+) {
+    for event in fired_events.read() {
+        let Ok((owner, power_ups)) = owner_query.get(event.owner) else {
+            continue;
+        };
+        let Some(def) = armory.get(event.weapon, &weapon_assets) else {
+            continue;
+        };
+        let ghost = power_ups.has(PowerUp::GhostBullets);
+        let shot = def
+            .projectile_angles(event.angle)
+            .map(|angle| BulletState {
+                position: event.position,
+                angle,
+                owner: owner.0,
+                id: 0,
+                weapon: event.weapon,
+                ghost,
+            })
+            .collect();
+        let message = ClientMessage::Fire(shot);
+        let fire_data = bincode::serialize(&message).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, fire_data);
     }
-    if keyboard_input.pressed(KeyCode::KeyD) {
-        player_state.position.x += 1.0;
-        moved = true;
+}
+
+// Pass on the beams fired by tanks driven here, so every client traces them
+fn send_laser_system(
+    mut client: ResMut<RenetClient>,
+    mut laser_events: EventReader<LaserFired>,
+    owner_query: Query<&NetworkId, Without<Remote>>,
+) {
+    for event in laser_events.read() {
+        let Ok(owner) = owner_query.get(event.owner) else {
+            continue;
+        };
+        let message = ClientMessage::Laser(BeamState {
+            origin: event.origin,
+            angle: event.angle,
+            owner: owner.0,
+            weapon: event.weapon,
+            id: 0,
+        });
+        let laser_data = bincode::serialize(&message).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, laser_data);
     }
+}
 
-    if moved {
-        println!("Sending movement: {:?}", player_state.position);
-        let movement_data = bincode::serialize(&*player_state).unwrap();
-        client.send_message(DefaultChannel::ReliableOrdered, movement_data);
+// Tell the server once a bullet fired by a player driven here is gone,
+// whether it ran out of lifetime or hit a tank, so both count the same bullets
+fn send_expired_system(
+    mut client: ResMut<RenetClient>,
+    mut expired_events: EventReader<BulletExpired>,
+    mut removed_bullets: RemovedComponents<Bullet>,
+    mut driven_bullets: ResMut<DrivenBullets>,
+) {
+    // Expired bullets are reported the frame they expire, the others once
+    // they are gone after hitting a tank
+    let gone = expired_events
+        .read()
+        .map(|event| event.bullet)
+        .chain(removed_bullets.read());
+    for bullet in gone {
+        let Some(id) = driven_bullets.0.remove(&bullet) else {
+            continue;
+        };
+        let expired_data = bincode::serialize(&ClientMessage::BulletExpired(id)).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, expired_data);
     }
 }
 
@@ -119,3 +358,710 @@ fn receive_game_state_system(mut client: ResMut<RenetClient>, mut game_state: Re
         }
     }
 }
+
+// Ask the server for the pickups tanks driven here drive over, once each
+fn send_pickup_system(
+    mut client: ResMut<RenetClient>,
+    game_state: Res<GameState>,
+    mut collected_events: EventReader<PickupCollected>,
+    tank_query: Query<&NetworkId, Without<Remote>>,
+    mut requested: Local<HashSet<u32>>,
+) {
+    requested.retain(|id| game_state.pickups.iter().any(|pickup| pickup.id == *id));
+    for event in collected_events.read() {
+        let Ok(player) = tank_query.get(event.tank) else {
+            continue;
+        };
+        if !requested.insert(event.id) {
+            continue;
+        }
+        let message = ClientMessage::CollectPickup(player.0, event.id);
+        let pickup_data = bincode::serialize(&message).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, pickup_data);
+    }
+}
+
+// Ask the server to place the mines and barriers tanks driven here put
+// down, and tell it which of its mines went off
+fn send_deploy_system(
+    mut client: ResMut<RenetClient>,
+    mut deployed_events: EventReader<Deployed>,
+    mut detonated_events: EventReader<MineDetonated>,
+    owner_query: Query<&NetworkId>,
+) {
+    let deployed = deployed_events.read().filter_map(|event| {
+        let owner = owner_query.get(event.owner).ok()?;
+        Some(ClientMessage::Deploy(DeployableState {
+            kind: event.kind,
+            position: event.position,
+            wall_type: event.wall_type,
+            owner: owner.0,
+            id: 0,
+            placed_at: 0.,
+        }))
+    });
+    let detonated = detonated_events
+        .read()
+        .filter_map(|event| event.id.map(ClientMessage::Detonate));
+    for message in deployed.chain(detonated) {
+        let deploy_data = bincode::serialize(&message).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, deploy_data);
+    }
+}
+
+// Tell the server once a tank driven here is destroyed, and by whose shot.
+// Tanks are gone by the time the event is read, so who played which tank is
+// noted as they spawn; entities are not reused while their old id is around.
+fn send_destroyed_system(
+    mut client: ResMut<RenetClient>,
+    mut destroyed_events: EventReader<TankDestroyed>,
+    new_tanks: Query<(Entity, &NetworkId), Added<NetworkId>>,
+    state: Res<State<AppState>>,
+    mut players: Local<HashMap<Entity, u64>>,
+) {
+    if *state.get() == AppState::Lobby {
+        players.clear();
+    }
+    players.extend(new_tanks.iter().map(|(tank, id)| (tank, id.0)));
+
+    for event in destroyed_events.read() {
+        let Some(player) = players.get(&event.tank) else {
+            continue;
+        };
+        let killer = players.get(&event.killer).copied();
+        let message = ClientMessage::Destroyed(*player, killer);
+        let destroyed_data = bincode::serialize(&message).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, destroyed_data);
+    }
+}
+
+/// What `send_lobby_system` has already asked the server for.
+#[derive(Default)]
+struct LobbySent {
+    name: bool,
+    class: Option<TankClass>,
+    settings: Option<MatchSettings>,
+}
+
+/// Next palette index after `color` that nobody but `client_id` has taken.
+fn next_free_color(game_state: &GameState, client_id: u64, color: u8) -> u8 {
+    (1..PALETTE_SIZE)
+        .map(|step| (color + step) % PALETTE_SIZE)
+        .find(|color| {
+            !game_state
+                .lobby
+                .iter()
+                .any(|(other, entry)| *other != client_id && entry.color == *color)
+        })
+        .unwrap_or(color)
+}
+
+// Tell the server what this player picks in the lobby: the first local
+// player's class, Enter to ready up and C for the next free colour. The
+// host also picks the mode, friendly fire and map.
+fn send_lobby_system(
+    mut client: ResMut<RenetClient>,
+    (keys, bindings): (Res<ButtonInput<KeyCode>>, Res<Bindings>),
+    (game_state, profile, local_players): (Res<GameState>, Res<LobbyProfile>, Res<LocalPlayers>),
+    local_client: Res<LocalClient>,
+    mut sent: Local<LobbySent>,
+) {
+    let Some(entry) = game_state.lobby.get(&local_client.id) else {
+        return;
+    };
+    let is_host = game_state.host == Some(local_client.id);
+    let mut requests = Vec::new();
+
+    if !sent.name {
+        requests.extend(profile.name.clone().map(LobbyRequest::Name));
+        sent.name = true;
+    }
+    // Also covers the class picked before the lobby entry showed up
+    if let Some(slot) = local_players.0.first() {
+        if sent.class != Some(slot.class) {
+            requests.push(LobbyRequest::Class(slot.class));
+            sent.class = Some(slot.class);
+        }
+    }
+    if keys.just_pressed(bindings.menu.ready) {
+        requests.push(LobbyRequest::Ready(!entry.ready));
+    }
+    if keys.just_pressed(bindings.menu.color) {
+        requests.push(LobbyRequest::Color(next_free_color(
+            &game_state,
+            local_client.id,
+            entry.color,
+        )));
+    }
+
+    let mut settings = MatchSettings {
+        mode: game_state.mode,
+        friendly_fire: game_state.friendly_fire,
+        map: game_state.map,
+        seed: game_state.seed,
+        bots: game_state.bot_difficulty,
+    };
+    if keys.just_pressed(bindings.menu.mode) {
+        let current = GameMode::ALL
+            .iter()
+            .position(|mode| *mode == settings.mode)
+            .unwrap_or(0);
+        settings.mode = GameMode::ALL[(current + 1) % GameMode::ALL.len()];
+    }
+    if keys.just_pressed(bindings.menu.friendly_fire) {
+        settings.friendly_fire = !settings.friendly_fire;
+    }
+    if keys.just_pressed(bindings.menu.map) {
+        settings.map = match settings.map {
+            MapChoice::Shuffled => MapChoice::Fixed,
+            MapChoice::Fixed => MapChoice::Shuffled,
+        };
+    }
+    if keys.just_pressed(bindings.menu.bot_difficulty) {
+        let current = Difficulty::ALL
+            .iter()
+            .position(|difficulty| *difficulty == settings.bots)
+            .unwrap_or(0);
+        settings.bots = Difficulty::ALL[(current + 1) % Difficulty::ALL.len()];
+    }
+    if profile.seed.is_some() {
+        settings.seed = profile.seed;
+    }
+    // Only once per change, the server takes a moment to answer
+    let current = MatchSettings {
+        mode: game_state.mode,
+        friendly_fire: game_state.friendly_fire,
+        map: game_state.map,
+        seed: game_state.seed,
+        bots: game_state.bot_difficulty,
+    };
+    if is_host && settings != current && sent.settings.as_ref() != Some(&settings) {
+        sent.settings = Some(settings.clone());
+        requests.push(LobbyRequest::Settings(settings));
+    }
+
+    for request in requests {
+        let lobby_data = bincode::serialize(&ClientMessage::Lobby(request)).unwrap();
+        client.send_message(DefaultChannel::ReliableOrdered, lobby_data);
+    }
+}
+
+// Show who is in the server's lobby and whether they are ready
+fn sync_lobby_system(
+    (game_state, palette, local_client): (Res<GameState>, Res<Palette>, Res<LocalClient>),
+    (state, bindings): (Res<State<AppState>>, Res<Bindings>),
+    mut roster: ResMut<LobbyRoster>,
+) {
+    if !game_state.is_changed() && !palette.is_changed() && !bindings.is_changed() {
+        return;
+    }
+    if *state.get() != AppState::Lobby {
+        roster.0.clear();
+        return;
+    }
+
+    let mut members: Vec<_> = game_state.lobby.iter().collect();
+    members.sort_by_key(|(client_id, _)| **client_id);
+    let map = match game_state.map {
+        MapChoice::Shuffled => "new maze every round",
+        MapChoice::Fixed => "one maze all match",
+    };
+    let mut settings = match game_state.seed {
+        Some(seed) => format!("{map}, seed {seed}"),
+        None => map.to_string(),
+    };
+    if game_state.lobby.keys().any(|client_id| is_bot(*client_id)) {
+        settings += &format!(
+            ", {} bots played by the host",
+            game_state.bot_difficulty.name()
+        );
+    }
+    let mut lines = vec![settings];
+    for (client_id, entry) in members {
+        let mut line = format!(
+            "{}  {:?}  {}  {}",
+            entry.name,
+            entry.class,
+            palette.color_name(PlayerColor(entry.color)),
+            if entry.ready { "ready" } else { "not ready" }
+        );
+        if game_state.host == Some(*client_id) {
+            line += "  host";
+        }
+        if *client_id == local_client.id {
+            line += "  (you)";
+        }
+        lines.push(line);
+    }
+    lines.push(String::new());
+    let menu = &bindings.menu;
+    lines.push(format!(
+        "Turn left / right to pick a class, {:?} for your colour, {:?} when ready",
+        menu.color, menu.ready
+    ));
+    if game_state.host == Some(local_client.id) {
+        lines.push(format!(
+            "{:?} for the game mode, {:?} for friendly fire, {:?} for the map, {:?} for the bots",
+            menu.mode, menu.friendly_fire, menu.map, menu.bot_difficulty
+        ));
+    }
+    lines.push(format!("F5 to change colours, {} now", palette.name()));
+    roster.0 = lines;
+}
+
+// Follow the server from phase to phase once past the main menu
+fn sync_phase_system(
+    game_state: Res<GameState>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut maze_seed: ResMut<MazeSeed>,
+    mut last_phase: Local<Option<MatchPhase>>,
+) {
+    if *state.get() == AppState::MainMenu {
+        *last_phase = None;
+        return;
+    }
+    if *last_phase == Some(game_state.phase) {
+        return;
+    }
+    maze_seed.0 = game_state.maze_seed;
+
+    // The maze and the tanks go in on entering the countdown, so a client
+    // that joins mid-round, or missed the countdown, goes through it first
+    let current = *state.get();
+    let missed_countdown = match AppState::from(game_state.phase) {
+        AppState::InRound => !matches!(current, AppState::Countdown | AppState::InRound),
+        AppState::RoundOver => !matches!(
+            current,
+            AppState::Countdown | AppState::InRound | AppState::RoundOver
+        ),
+        _ => false,
+    };
+    if missed_countdown {
+        next_state.set(AppState::Countdown);
+        return;
+    }
+    *last_phase = Some(game_state.phase);
+    next_state.set(game_state.phase.into());
+}
+
+/// What `sync_players_system` needs to build a tank.
+type TankParts<'w> = (
+    Res<'w, TankClasses>,
+    Res<'w, Assets<TankClassDef>>,
+    Res<'w, LocalPlayers>,
+    Res<'w, AmmoRules>,
+);
+
+// Give everyone in the round a tank. This client's own and, on the host,
+// the bots go in during the countdown and are driven here like local tanks;
+// everyone else's follow the server until it says they are out
+fn sync_players_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    (game_state, local_client, state): (Res<GameState>, Res<LocalClient>, Res<State<AppState>>),
+    (classes, class_assets, local_players, ammo_rules): TankParts,
+    mut tank_query: Query<(Entity, &NetworkId, Option<&Remote>, &mut Transform), With<Tank>>,
+) {
+    let in_match = matches!(
+        state.get(),
+        AppState::Countdown | AppState::InRound | AppState::RoundOver
+    );
+    if !game_state.is_changed() || !in_match {
+        return;
+    }
+
+    for (entity, id, remote, mut transform) in tank_query.iter_mut() {
+        let left = !game_state.players.contains_key(&id.0);
+        if left || (remote.is_some() && !game_state.alive.contains(&id.0)) {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if let (Some(player), Some(_)) = (game_state.players.get(&id.0), remote) {
+            transform.translation = player.position.extend(transform.translation.z);
+            transform.rotation = Quat::from_rotation_z(player.rotation.to_radians());
+        }
+    }
+
+    for (client_id, player) in game_state.players.iter() {
+        if !game_state.alive.contains(client_id)
+            || tank_query.iter().any(|(_, id, ..)| id.0 == *client_id)
+        {
+            continue;
+        }
+        // A tank driven here that has gone missing was destroyed, it comes
+        // back next round
+        let driven = drives(&game_state, local_client.id, *client_id);
+        if driven && *state.get() != AppState::Countdown {
+            continue;
+        }
+        let Some(entry) = game_state.lobby.get(client_id) else {
+            continue;
+        };
+        let Some(def) = classes.get(entry.class, &class_assets) else {
+            continue;
+        };
+
+        let transform = Transform::from_translation(player.position.extend(0.))
+            .with_rotation(Quat::from_rotation_z(player.rotation.to_radians()));
+        let tank = spawn_tank(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &ammo_rules,
+            entry.class,
+            def,
+            transform,
+        );
+        let mut tank = commands.entity(tank);
+        tank.insert((NetworkId(*client_id), PlayerColor(entry.color)));
+        if let Some(team) = game_state.teams.get(client_id) {
+            tank.insert(*team);
+        }
+        if *client_id == local_client.id {
+            if let Some(slot) = local_players.0.first() {
+                tank.insert((Player(0), slot.controls.clone()));
+            }
+        } else if driven {
+            let difficulty = game_state.bot_difficulty;
+            tank.insert((Controls::Bot(difficulty), Bot::new(difficulty)));
+        } else {
+            tank.insert((Remote, RigidBody::KinematicPositionBased))
+                .remove::<(Health, Gun, TankInput, KinematicCharacterController)>();
+        }
+    }
+}
+
+/// What `sync_bullets_system` needs to build a bullet.
+type BulletParts<'w> = (
+    Res<'w, Armory>,
+    Res<'w, Assets<WeaponDef>>,
+    Res<'w, BulletLifetime>,
+);
+
+// Fire the bullets the server has accepted, each one once
+#[allow(clippy::too_many_arguments)]
+fn sync_bullets_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    (game_state, local_client, state): (Res<GameState>, Res<LocalClient>, Res<State<AppState>>),
+    (armory, weapon_assets, lifetime): BulletParts,
+    tank_query: Query<(Entity, &NetworkId), With<Tank>>,
+    mut driven_bullets: ResMut<DrivenBullets>,
+    mut spawned: Local<HashSet<u32>>,
+) {
+    let in_match = matches!(
+        state.get(),
+        AppState::Countdown | AppState::InRound | AppState::RoundOver
+    );
+    if !game_state.is_changed() || !in_match {
+        return;
+    }
+
+    // Forget bullets the server has dropped, their ids are not handed out again
+    spawned.retain(|id| game_state.bullets.iter().any(|bullet| bullet.id == *id));
+    for bullet_state in game_state.bullets.iter() {
+        if spawned.contains(&bullet_state.id) {
+            continue;
+        }
+        let Some(def) = armory.get(bullet_state.weapon, &weapon_assets) else {
+            continue;
+        };
+        spawned.insert(bullet_state.id);
+        // A shooter destroyed since firing can no longer be hit by its own bullet
+        let owner = tank_query
+            .iter()
+            .find(|(_, id)| id.0 == bullet_state.owner)
+            .map_or(Entity::PLACEHOLDER, |(tank, _)| tank);
+        let mut bullet = Bullet::new(
+            bullet_state.angle,
+            owner,
+            bullet_state.weapon,
+            def,
+            def.lifetime(*lifetime),
+        );
+        if bullet_state.ghost {
+            bullet.make_ghost();
+        }
+        let entity = spawn_bullet(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            bullet_state.position,
+            bullet,
+        );
+        if drives(&game_state, local_client.id, bullet_state.owner) {
+            driven_bullets.0.insert(entity, bullet_state.id);
+        }
+    }
+}
+
+// Mirror the server's mines and barriers: put in the ones placed since, take
+// out the ones gone. One that went off or crumbled here first is not put back.
+#[allow(clippy::too_many_arguments)]
+fn sync_deployables_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut wall_grid: ResMut<WallGrid>,
+    (game_state, state): (Res<GameState>, Res<State<AppState>>),
+    tank_query: Query<(Entity, &NetworkId), With<Tank>>,
+    mine_query: Query<(Entity, &Mine)>,
+    barrier_query: Query<(Entity, &Barrier)>,
+    mut spawned: Local<HashSet<u32>>,
+) {
+    let in_match = matches!(
+        state.get(),
+        AppState::Countdown | AppState::InRound | AppState::RoundOver
+    );
+    if !game_state.is_changed() || !in_match {
+        return;
+    }
+
+    let placed = |id: Option<u32>| {
+        id.is_some_and(|id| game_state.deployables.iter().any(|placed| placed.id == id))
+    };
+    for (entity, mine) in mine_query.iter() {
+        if mine.id.is_some() && !placed(mine.id) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for (entity, barrier) in barrier_query.iter() {
+        if barrier.id.is_some() && !placed(barrier.id) {
+            wall_grid.remove(entity);
+            commands.entity(entity).despawn();
+        }
+    }
+
+    spawned.retain(|id| placed(Some(*id)));
+    for deployable in game_state.deployables.iter() {
+        if !spawned.insert(deployable.id) {
+            continue;
+        }
+        let owner = tank_query
+            .iter()
+            .find(|(_, id)| id.0 == deployable.owner)
+            .map_or(Entity::PLACEHOLDER, |(tank, _)| tank);
+        match (deployable.kind, deployable.wall_type) {
+            (DeployableKind::Mine, _) => {
+                let mine = Mine {
+                    id: Some(deployable.id),
+                    ..Mine::new(owner)
+                };
+                spawn_mine(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    mine,
+                    deployable.position,
+                );
+            }
+            (DeployableKind::Barrier, Some(wall_type)) => {
+                let barrier = Barrier {
+                    id: Some(deployable.id),
+                    ..Barrier::new(owner)
+                };
+                spawn_barrier(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    &mut wall_grid,
+                    barrier,
+                    deployable.position,
+                    wall_type,
+                );
+            }
+            (DeployableKind::Barrier, None) => {}
+        }
+    }
+}
+
+/// The name a client picked in the lobby.
+fn player_name(game_state: &GameState, client_id: u64) -> String {
+    game_state
+        .lobby
+        .get(&client_id)
+        .map_or_else(|| format!("Player {client_id}"), |entry| entry.name.clone())
+}
+
+// Show the server's scores and any kills not seen yet
+fn sync_scores_system(
+    game_state: Res<GameState>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut kill_feed: ResMut<KillFeed>,
+    mut last_kill: Local<Option<u32>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    scoreboard.0 = game_state
+        .scores
+        .iter()
+        .map(|(client_id, score)| (player_name(&game_state, *client_id), score.clone()))
+        .collect();
+    for kill in game_state.kill_feed.iter() {
+        if last_kill.is_some_and(|last_kill| kill.id <= last_kill) {
+            continue;
+        }
+        let killer = kill.killer.map(|killer| player_name(&game_state, killer));
+        kill_feed.push(killer.as_deref(), &player_name(&game_state, kill.victim));
+        *last_kill = Some(kill.id);
+    }
+}
+
+// Take the server's mode and every player's team and colour, and move the
+// flags as it says
+fn sync_mode_system(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    mut mode_rules: ResMut<ModeRules>,
+    mut tank_query: Query<(Entity, &NetworkId, Option<&Team>, &mut PlayerColor), With<Tank>>,
+    mut flag_query: Query<(&Flag, &mut Transform)>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    if mode_rules.mode != game_state.mode || mode_rules.friendly_fire != game_state.friendly_fire {
+        mode_rules.mode = game_state.mode;
+        mode_rules.friendly_fire = game_state.friendly_fire;
+    }
+    for (tank, id, team, mut color) in tank_query.iter_mut() {
+        match game_state.teams.get(&id.0) {
+            Some(server_team) if team != Some(server_team) => {
+                commands.entity(tank).insert(*server_team);
+            }
+            None if team.is_some() => {
+                commands.entity(tank).remove::<Team>();
+            }
+            _ => {}
+        }
+        if let Some(entry) = game_state.lobby.get(&id.0) {
+            color.set_if_neq(PlayerColor(entry.color));
+        }
+    }
+    for (flag, mut transform) in flag_query.iter_mut() {
+        let Some(state) = game_state
+            .flags
+            .iter()
+            .find(|state| state.team == flag.team)
+        else {
+            continue;
+        };
+        let (x, y) = flag.team.base_cell();
+        let position = state
+            .carrier
+            .and_then(|carrier| game_state.players.get(&carrier))
+            .map_or(cell_center(x, y), |player| player.position);
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+// Mirror the server's pickups: spawn the new ones, drop the ones taken
+fn sync_pickups_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    game_state: Res<GameState>,
+    pickup_query: Query<(Entity, &Pickup)>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    for (entity, pickup) in pickup_query.iter() {
+        if !game_state.pickups.iter().any(|state| state.id == pickup.id) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for state in game_state.pickups.iter() {
+        if pickup_query.iter().any(|(_, pickup)| pickup.id == state.id) {
+            continue;
+        }
+        let pickup = Pickup {
+            id: state.id,
+            kind: state.kind,
+        };
+        let cell = (state.cell.0 as usize, state.cell.1 as usize);
+        spawn_pickup(&mut commands, &mut meshes, &mut materials, pickup, cell);
+    }
+}
+
+/// What `sync_collected_system` needs to know about a tank taking a pickup.
+type CollectingTank<'a> = (
+    Entity,
+    &'a NetworkId,
+    &'a mut PowerUps,
+    &'a mut EquippedWeapon,
+    Option<&'a mut TemporaryWeapon>,
+);
+
+// Give tanks driven here the pickups the server let them take
+fn sync_collected_system(
+    mut commands: Commands,
+    (game_state, local_client, rules): (Res<GameState>, Res<LocalClient>, Res<PickupRules>),
+    mut tank_query: Query<CollectingTank, Without<Remote>>,
+    mut last_collected: Local<Option<u32>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+
+    for taken in game_state.collected.iter() {
+        if last_collected.is_some_and(|last_collected| taken.id <= last_collected) {
+            continue;
+        }
+        *last_collected = Some(taken.id);
+        if !drives(&game_state, local_client.id, taken.player) {
+            continue;
+        }
+        let tank = tank_query
+            .iter_mut()
+            .find(|(_, id, ..)| id.0 == taken.player);
+        let Some((tank, _, mut power_ups, mut weapon, temporary)) = tank else {
+            continue;
+        };
+        let taker = (power_ups.as_mut(), weapon.as_mut(), temporary);
+        apply_pickup(&mut commands, tank, taken.kind, rules.duration, taker);
+    }
+}
+
+// Trace the beams other clients' tanks fired here too, so they hit the tanks
+// driven here. Ones fired while out of a match are skipped.
+fn sync_beams_system(
+    (game_state, local_client, state): (Res<GameState>, Res<LocalClient>, Res<State<AppState>>),
+    tank_query: Query<(Entity, &NetworkId), With<Tank>>,
+    mut laser_events: EventWriter<LaserFired>,
+    mut last_beam: Local<Option<u32>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let in_match = matches!(
+        state.get(),
+        AppState::Countdown | AppState::InRound | AppState::RoundOver
+    );
+
+    for beam in game_state.beams.iter() {
+        if last_beam.is_some_and(|last_beam| beam.id <= last_beam) {
+            continue;
+        }
+        *last_beam = Some(beam.id);
+        if !in_match || drives(&game_state, local_client.id, beam.owner) {
+            continue;
+        }
+        let owner = tank_query
+            .iter()
+            .find(|(_, id)| id.0 == beam.owner)
+            .map_or(Entity::PLACEHOLDER, |(tank, _)| tank);
+        laser_events.send(LaserFired {
+            owner,
+            weapon: beam.weapon,
+            origin: beam.origin,
+            angle: beam.angle,
+        });
+    }
+}
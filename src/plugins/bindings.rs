@@ -0,0 +1,341 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Read on startup and written back after every rebind.
+const BINDINGS_PATH: &str = "bindings.ron";
+
+/// Something a player can ask their tank to do, independent of which key does it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Forward,
+    Back,
+    TurnLeft,
+    TurnRight,
+    Fire,
+    SwitchWeapon,
+    LayMine,
+    PlaceBarrier,
+    /// Switches every tank here between hull and turret aiming
+    ToggleAim,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Forward,
+        Action::Back,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Fire,
+        Action::SwitchWeapon,
+        Action::LayMine,
+        Action::PlaceBarrier,
+        Action::ToggleAim,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Forward => "Forward",
+            Action::Back => "Back",
+            Action::TurnLeft => "Turn left",
+            Action::TurnRight => "Turn right",
+            Action::Fire => "Fire",
+            Action::SwitchWeapon => "Switch weapon",
+            Action::LayMine => "Lay mine",
+            Action::PlaceBarrier => "Place barrier",
+            Action::ToggleAim => "Toggle aim",
+        }
+    }
+}
+
+/// Keys one player drives with on a shared keyboard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBindings {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub turn_left: KeyCode,
+    pub turn_right: KeyCode,
+    pub fire: KeyCode,
+    pub switch_weapon: KeyCode,
+    pub lay_mine: KeyCode,
+    pub place_barrier: KeyCode,
+    /// Missing from files saved before it could be rebound
+    #[serde(default = "default_toggle_aim")]
+    pub toggle_aim: KeyCode,
+}
+
+fn default_toggle_aim() -> KeyCode {
+    KeyBindings::WASD.toggle_aim
+}
+
+impl KeyBindings {
+    pub const WASD: KeyBindings = KeyBindings {
+        forward: KeyCode::KeyW,
+        back: KeyCode::KeyS,
+        turn_left: KeyCode::KeyA,
+        turn_right: KeyCode::KeyD,
+        fire: KeyCode::KeyQ,
+        switch_weapon: KeyCode::KeyE,
+        lay_mine: KeyCode::KeyR,
+        place_barrier: KeyCode::KeyF,
+        toggle_aim: KeyCode::KeyT,
+    };
+
+    pub const ARROWS: KeyBindings = KeyBindings {
+        forward: KeyCode::ArrowUp,
+        back: KeyCode::ArrowDown,
+        turn_left: KeyCode::ArrowLeft,
+        turn_right: KeyCode::ArrowRight,
+        fire: KeyCode::KeyM,
+        switch_weapon: KeyCode::Comma,
+        lay_mine: KeyCode::Period,
+        place_barrier: KeyCode::Slash,
+        toggle_aim: KeyCode::ShiftRight,
+    };
+
+    pub fn key(&self, action: Action) -> KeyCode {
+        match action {
+            Action::Forward => self.forward,
+            Action::Back => self.back,
+            Action::TurnLeft => self.turn_left,
+            Action::TurnRight => self.turn_right,
+            Action::Fire => self.fire,
+            Action::SwitchWeapon => self.switch_weapon,
+            Action::LayMine => self.lay_mine,
+            Action::PlaceBarrier => self.place_barrier,
+            Action::ToggleAim => self.toggle_aim,
+        }
+    }
+
+    fn key_mut(&mut self, action: Action) -> &mut KeyCode {
+        match action {
+            Action::Forward => &mut self.forward,
+            Action::Back => &mut self.back,
+            Action::TurnLeft => &mut self.turn_left,
+            Action::TurnRight => &mut self.turn_right,
+            Action::Fire => &mut self.fire,
+            Action::SwitchWeapon => &mut self.switch_weapon,
+            Action::LayMine => &mut self.lay_mine,
+            Action::PlaceBarrier => &mut self.place_barrier,
+            Action::ToggleAim => &mut self.toggle_aim,
+        }
+    }
+
+    /// Binds `key` to `action`. An action that already had `key` takes over
+    /// the old key of `action`, so no two actions ever share a key.
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        let old = self.key(action);
+        if let Some(other) = Action::ALL
+            .into_iter()
+            .find(|other| self.key(*other) == key)
+        {
+            *self.key_mut(other) = old;
+        }
+        *self.key_mut(action) = key;
+    }
+
+    pub fn pressed(&self, keys: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keys.pressed(self.key(action))
+    }
+
+    pub fn just_pressed(&self, keys: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keys.just_pressed(self.key(action))
+    }
+}
+
+/// Keys for the lobby and the match settings picked there, shared by
+/// everyone at the keyboard. Keys missing from the file keep their defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MenuBindings {
+    /// Steps through the game modes
+    pub mode: KeyCode,
+    pub friendly_fire: KeyCode,
+    /// Readies up, or starts the match when playing offline
+    pub ready: KeyCode,
+    /// Steps to the next colour nobody else has
+    pub color: KeyCode,
+    /// Switches between a new maze every round and one for the whole match
+    pub map: KeyCode,
+    /// Steps through the bots' difficulties
+    pub bot_difficulty: KeyCode,
+}
+
+impl Default for MenuBindings {
+    fn default() -> Self {
+        Self {
+            mode: KeyCode::KeyG,
+            friendly_fire: KeyCode::KeyH,
+            ready: KeyCode::Enter,
+            color: KeyCode::KeyC,
+            map: KeyCode::KeyM,
+            bot_difficulty: KeyCode::KeyV,
+        }
+    }
+}
+
+/// Every keyboard scheme, as stored in `bindings.ron`. Keyboard players
+/// refer to theirs by index.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct Bindings {
+    pub keyboard: Vec<KeyBindings>,
+    /// Missing from files saved before there were menu keys
+    #[serde(default)]
+    pub menu: MenuBindings,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            keyboard: vec![KeyBindings::WASD, KeyBindings::ARROWS],
+            menu: MenuBindings::default(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Bindings from `bindings.ron`, or the defaults if it is missing or broken.
+    fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(BINDINGS_PATH) else {
+            return Self::default();
+        };
+        match ron::from_str::<Bindings>(&contents) {
+            Ok(bindings) if !bindings.keyboard.is_empty() => bindings,
+            Ok(_) => Self::default(),
+            Err(err) => {
+                warn!("ignoring {BINDINGS_PATH}: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(BINDINGS_PATH, contents).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!("could not save {BINDINGS_PATH}: {err}");
+        }
+    }
+
+    /// Scheme `index`, falling back to the first one.
+    pub fn scheme(&self, index: usize) -> &KeyBindings {
+        self.keyboard.get(index).unwrap_or(&self.keyboard[0])
+    }
+}
+
+/// Keys that start rebinding keyboard scheme 0, 1, ...
+const REBIND_KEYS: [KeyCode; 2] = [KeyCode::F1, KeyCode::F2];
+
+/// Set while a player is walking through their actions picking new keys.
+#[derive(Resource, Debug, Default)]
+pub struct Rebinding(Option<RebindStep>);
+
+#[derive(Debug, Clone, Copy)]
+struct RebindStep {
+    scheme: usize,
+    /// Index into `Action::ALL` of the action waiting for a key
+    action: usize,
+}
+
+impl Rebinding {
+    pub fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+#[derive(Component)]
+struct RebindPrompt;
+
+pub struct BindingsPlugin;
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bindings::load())
+            .init_resource::<Rebinding>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, (rebind_system, rebind_prompt_system).chain());
+    }
+}
+
+pub fn not_rebinding(rebinding: Res<Rebinding>) -> bool {
+    !rebinding.is_active()
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            left: Val::Px(10.),
+            ..default()
+        },
+        RebindPrompt,
+    ));
+}
+
+/// F1/F2 walk the matching keyboard scheme through every action, taking the
+/// next key pressed for each. Escape stops early and keeps what was bound so far.
+fn rebind_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut bindings: ResMut<Bindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let Some(step) = rebinding.0 else {
+        let scheme = REBIND_KEYS
+            .iter()
+            .position(|key| keys.just_pressed(*key))
+            .filter(|scheme| *scheme < bindings.keyboard.len());
+        if let Some(scheme) = scheme {
+            rebinding.0 = Some(RebindStep { scheme, action: 0 });
+        }
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        bindings.save();
+        return;
+    }
+    let Some(key) = keys.get_just_pressed().next() else {
+        return;
+    };
+
+    bindings.keyboard[step.scheme].bind(Action::ALL[step.action], *key);
+    let next = step.action + 1;
+    if next < Action::ALL.len() {
+        rebinding.0 = Some(RebindStep {
+            action: next,
+            ..step
+        });
+    } else {
+        rebinding.0 = None;
+        bindings.save();
+    }
+}
+
+fn rebind_prompt_system(
+    rebinding: Res<Rebinding>,
+    bindings: Res<Bindings>,
+    mut prompt_query: Query<&mut Text, With<RebindPrompt>>,
+) {
+    if !rebinding.is_changed() {
+        return;
+    }
+    let Ok(mut text) = prompt_query.get_single_mut() else {
+        return;
+    };
+    text.0 = match rebinding.0 {
+        Some(step) => {
+            let action = Action::ALL[step.action];
+            format!(
+                "Keyboard {}: press a key for {} (now {:?}), Esc to finish",
+                step.scheme + 1,
+                action.name(),
+                bindings.keyboard[step.scheme].key(action)
+            )
+        }
+        None => String::new(),
+    };
+}
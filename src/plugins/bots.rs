@@ -0,0 +1,288 @@
+use bevy::{input::InputSystem, prelude::*};
+pub use shared::lobby::Difficulty;
+
+use crate::{
+    collider::Collider,
+    constants::{BULLET_OFFSET, GRID_CELL_SIZE},
+    grid::{cell_center, clamped_cell_at},
+    walls::{Maze, Wall},
+};
+
+use super::{
+    bindings::{not_rebinding, Bindings},
+    classes::TankClass,
+    collision::{solve_bounce_shots, ShotProblem},
+    game_flow::{driven_locally, AppState},
+    input::{Controls, TankInput},
+    modes::Team,
+    players::{LocalPlayers, PlayerSlot, MAX_LOCAL_PLAYERS},
+    shooting::BulletLifetime,
+    tank::{turret_angle, AimMode, Tank, Turret},
+    weapons::{Armory, EquippedWeapon, WeaponDef},
+};
+
+/// How far ahead a bot looks for a shot.
+const SHOT_RANGE: f32 = 900.;
+/// How close to a cell's centre a bot gets before heading for the next one.
+const WAYPOINT_RADIUS: f32 = GRID_CELL_SIZE * 0.25;
+/// Degrees off course at which a bot steers at full lock.
+const FULL_STEER_ANGLE: f32 = 30.;
+/// Degrees off course beyond which a bot turns on the spot instead of driving.
+const DRIVE_ANGLE: f32 = 60.;
+const ADD_BOT_KEY: KeyCode = KeyCode::KeyB;
+const REMOVE_BOT_KEY: KeyCode = KeyCode::KeyN;
+
+/// How a bot plays at each difficulty. The difficulties themselves are
+/// shared with the server.
+trait BotSkill {
+    /// Seconds between a bot's looks at the arena.
+    fn reaction_time(&self) -> f32;
+    /// Degrees the gun may be off the planned shot when the bot pulls the trigger.
+    fn aim_tolerance(&self) -> f32;
+    /// Wall bounces a bot will plan a shot around.
+    fn bounces(&self) -> u32;
+}
+
+impl BotSkill for Difficulty {
+    fn reaction_time(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 0.35,
+            Difficulty::Hard => 0.15,
+        }
+    }
+
+    fn aim_tolerance(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 12.,
+            Difficulty::Normal => 6.,
+            Difficulty::Hard => 3.,
+        }
+    }
+
+    fn bounces(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Normal => 1,
+            Difficulty::Hard => 2,
+        }
+    }
+}
+
+/// A tank driven by the computer. It hunts the nearest enemy through the
+/// maze and shoots once it finds a line to it, straight or off the walls.
+#[derive(Component, Debug)]
+pub struct Bot {
+    pub difficulty: Difficulty,
+    /// Cells still to drive through, the next one first
+    path: Vec<(usize, usize)>,
+    /// Angle in degrees of the shot the bot is lining up, if it found one
+    shot: Option<f32>,
+    think: Timer,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty) -> Self {
+        let mut think = Timer::from_seconds(difficulty.reaction_time(), TimerMode::Repeating);
+        // Look around on the first frame of the round
+        think.set_elapsed(think.duration());
+        Self {
+            difficulty,
+            path: Vec::new(),
+            shot: None,
+            think,
+        }
+    }
+}
+
+pub struct BotsPlugin;
+impl Plugin for BotsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            manage_bots_system.run_if(in_state(AppState::Lobby).and(driven_locally)),
+        )
+        .add_systems(
+            PreUpdate,
+            (bot_think_system, bot_drive_system)
+                .chain()
+                .after(InputSystem)
+                .run_if(in_state(AppState::InRound).and(not_rebinding)),
+        );
+    }
+}
+
+/// B adds a bot, N takes the last one out and V steps every bot through the
+/// difficulties, in the lobby.
+fn manage_bots_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut local_players: ResMut<LocalPlayers>,
+) {
+    if keys.just_pressed(ADD_BOT_KEY) && local_players.0.len() < MAX_LOCAL_PLAYERS {
+        let difficulty = local_players
+            .0
+            .iter()
+            .find_map(|slot| match slot.controls {
+                Controls::Bot(difficulty) => Some(difficulty),
+                _ => None,
+            })
+            .unwrap_or_default();
+        local_players.0.push(PlayerSlot {
+            controls: Controls::Bot(difficulty),
+            class: TankClass::default(),
+        });
+    }
+    if keys.just_pressed(REMOVE_BOT_KEY) {
+        if let Some(index) = local_players
+            .0
+            .iter()
+            .rposition(|slot| matches!(slot.controls, Controls::Bot(_)))
+        {
+            local_players.0.remove(index);
+        }
+    }
+    if keys.just_pressed(bindings.menu.bot_difficulty) {
+        for slot in local_players.0.iter_mut() {
+            if let Controls::Bot(difficulty) = &mut slot.controls {
+                let current = Difficulty::ALL
+                    .iter()
+                    .position(|other| other == difficulty)
+                    .unwrap_or(0);
+                *difficulty = Difficulty::ALL[(current + 1) % Difficulty::ALL.len()];
+            }
+        }
+    }
+}
+
+/// Every `reaction_time` seconds each bot picks the nearest enemy, plans a
+/// route to it through the maze and looks for a shot at it.
+fn bot_think_system(
+    time: Res<Time>,
+    (maze, lifetime): (Res<Maze>, Res<BulletLifetime>),
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    mut bot_query: Query<(Entity, &mut Bot, &Transform, &EquippedWeapon, Option<&Team>)>,
+    tank_query: Query<(Entity, &Transform, &Collider, Option<&Team>), With<Tank>>,
+    wall_query: Query<&Collider, With<Wall>>,
+) {
+    let walls: Vec<&Collider> = wall_query.iter().collect();
+
+    for (entity, mut bot, transform, weapon, team) in bot_query.iter_mut() {
+        if !bot.think.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let position = transform.translation.xy();
+        let target = tank_query
+            .iter()
+            .filter(|(other, _, _, other_team)| {
+                *other != entity && (team.is_none() || team != *other_team)
+            })
+            .min_by(|a, b| {
+                let a = a.1.translation.xy().distance_squared(position);
+                let b = b.1.translation.xy().distance_squared(position);
+                a.total_cmp(&b)
+            });
+        let Some((_, target_transform, target_collider, _)) = target else {
+            bot.path.clear();
+            bot.shot = None;
+            continue;
+        };
+
+        let Some(def) = armory.get(weapon.0, &weapon_assets) else {
+            continue;
+        };
+        let lifetime = def.lifetime(*lifetime);
+        let bounces = bot.difficulty.bounces();
+        let teammates: Vec<(Vec2, f32)> = tank_query
+            .iter()
+            .filter(|(other, _, _, other_team)| {
+                *other != entity && team.is_some() && team == *other_team
+            })
+            .map(|(_, transform, collider, _)| {
+                (
+                    transform.translation.xy(),
+                    collider.half_extents().max_element(),
+                )
+            })
+            .collect();
+        let problem = ShotProblem {
+            shooter: position,
+            muzzle_offset: BULLET_OFFSET,
+            target: target_transform.translation.xy(),
+            target_radius: target_collider.half_extents().min_element(),
+            avoid: &teammates,
+            max_bounces: lifetime
+                .max_bounces
+                .map_or(bounces, |max| bounces.min(max as u32)),
+            range: lifetime
+                .max_age
+                .map_or(SHOT_RANGE, |max_age| (max_age * def.speed).min(SHOT_RANGE)),
+            margin: Vec2::splat(def.size / 2.),
+        };
+        bot.shot = solve_bounce_shots(&problem, &walls)
+            .first()
+            .map(|shot| shot.angle);
+
+        let (from_x, from_y) = clamped_cell_at(position);
+        let (to_x, to_y) = clamped_cell_at(target_transform.translation.xy());
+        // The route starts with the bot's own cell so it centres up before
+        // turning into the next corridor instead of clipping the corner
+        bot.path = maze
+            .path(
+                (from_x as usize, from_y as usize),
+                (to_x as usize, to_y as usize),
+            )
+            .unwrap_or_default();
+    }
+}
+
+/// Shortest signed turn in degrees from `from` to `to`, in (-180, 180].
+fn turn_towards(from: f32, to: f32) -> f32 {
+    (to - from + 540.0).rem_euclid(360.0) - 180.0
+}
+
+/// Turns each bot's plan into `TankInput`: swing the gun onto a planned shot
+/// and fire, or otherwise drive along the route.
+fn bot_drive_system(
+    aim_mode: Res<AimMode>,
+    mut bot_query: Query<(&mut Bot, &Transform, Option<&Children>, &mut TankInput)>,
+    turret_query: Query<&Turret>,
+) {
+    for (mut bot, transform, children, mut input) in bot_query.iter_mut() {
+        *input = TankInput::default();
+        let position = transform.translation.xy();
+        let hull_angle = transform.rotation.to_euler(EulerRot::XYZ).2.to_degrees();
+
+        if let Some(shot) = bot.shot {
+            let gun_angle = hull_angle + turret_angle(children, &turret_query);
+            let off = turn_towards(gun_angle, shot);
+            match *aim_mode {
+                AimMode::Hull => {
+                    input.steer = (off / FULL_STEER_ANGLE).clamp(-1., 1.);
+                }
+                AimMode::Turret => {
+                    input.aim = Some(position + Vec2::from_angle(shot.to_radians()) * SHOT_RANGE);
+                }
+            }
+            input.fire = off.abs() <= bot.difficulty.aim_tolerance();
+            continue;
+        }
+
+        while bot
+            .path
+            .first()
+            .is_some_and(|(x, y)| cell_center(*x, *y).distance(position) <= WAYPOINT_RADIUS)
+        {
+            bot.path.remove(0);
+        }
+        let Some(&(x, y)) = bot.path.first() else {
+            continue;
+        };
+        let to_waypoint = cell_center(x, y) - position;
+        let off = turn_towards(hull_angle, to_waypoint.y.atan2(to_waypoint.x).to_degrees());
+        input.steer = (off / FULL_STEER_ANGLE).clamp(-1., 1.);
+        if off.abs() < DRIVE_ANGLE {
+            input.throttle = off.to_radians().cos();
+        }
+    }
+}
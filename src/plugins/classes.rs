@@ -0,0 +1,151 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+    utils::HashMap,
+};
+use serde::Deserialize;
+pub use shared::lobby::TankClass;
+
+use super::{
+    bindings::{Action, Bindings},
+    game_flow::AppState,
+    handling::TankStats,
+    input::action_just_pressed,
+    players::LocalPlayers,
+    weapons::WeaponKind,
+};
+
+/// A tank class as written in its `*.class.ron` file.
+#[derive(Deserialize)]
+struct TankClassFile {
+    name: String,
+    sprite: String,
+    /// Hull length and width in pixels
+    size: (f32, f32),
+    health: f32,
+    /// Weapons the tank can switch between, the first one is equipped on spawn
+    loadout: Vec<WeaponKind>,
+    /// Path of the `*.tank.ron` handling file
+    handling: String,
+}
+
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct TankClassDef {
+    pub name: String,
+    pub sprite: Handle<Image>,
+    pub size: Vec2,
+    pub health: f32,
+    pub loadout: Vec<WeaponKind>,
+    pub stats: Handle<TankStats>,
+}
+
+#[derive(Default)]
+pub struct TankClassLoader;
+
+impl AssetLoader for TankClassLoader {
+    type Asset = TankClassDef;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file: TankClassFile = ron::de::from_bytes(&bytes)?;
+        if file.loadout.is_empty() {
+            return Err(format!("tank class {} has an empty loadout", file.name).into());
+        }
+        Ok(TankClassDef {
+            sprite: load_context.load(file.sprite),
+            stats: load_context.load(file.handling),
+            name: file.name,
+            size: file.size.into(),
+            health: file.health,
+            loadout: file.loadout,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["class.ron"]
+    }
+}
+
+/// Every class definition, loaded on startup.
+#[derive(Resource, Debug, Default)]
+pub struct TankClasses(pub HashMap<TankClass, Handle<TankClassDef>>);
+
+impl TankClasses {
+    pub fn get<'a>(
+        &self,
+        class: TankClass,
+        assets: &'a Assets<TankClassDef>,
+    ) -> Option<&'a TankClassDef> {
+        assets.get(self.0.get(&class)?)
+    }
+}
+
+/// Weapons a tank can switch between.
+#[derive(Component, Debug, Clone)]
+pub struct Loadout(pub Vec<WeaponKind>);
+
+impl Loadout {
+    /// The weapon after `current`, wrapping around. Weapons outside the
+    /// loadout, like ones lent by pickups, go back to the first.
+    pub fn next(&self, current: WeaponKind) -> WeaponKind {
+        let next = self
+            .0
+            .iter()
+            .position(|kind| *kind == current)
+            .map_or(0, |index| index + 1);
+        self.0[next % self.0.len()]
+    }
+}
+
+pub struct ClassesPlugin;
+impl Plugin for ClassesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TankClassDef>()
+            .init_asset_loader::<TankClassLoader>()
+            .init_resource::<TankClasses>()
+            .add_systems(PreStartup, load_classes)
+            .add_systems(
+                Update,
+                select_class_system.run_if(in_state(AppState::Lobby)),
+            );
+    }
+}
+
+fn load_classes(asset_server: Res<AssetServer>, mut classes: ResMut<TankClasses>) {
+    for class in TankClass::ALL {
+        classes.0.insert(class, asset_server.load(class.path()));
+    }
+}
+
+/// In the lobby each player steps through the classes with their turn keys.
+fn select_class_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<Bindings>,
+    gamepads: Query<&Gamepad>,
+    mut local_players: ResMut<LocalPlayers>,
+) {
+    for slot in local_players.0.iter_mut() {
+        let pressed =
+            |action| action_just_pressed(&slot.controls, action, &keys, &bindings, &gamepads);
+        let step = if pressed(Action::TurnLeft) {
+            TankClass::ALL.len() - 1
+        } else if pressed(Action::TurnRight) {
+            1
+        } else {
+            continue;
+        };
+        let current = TankClass::ALL
+            .iter()
+            .position(|class| *class == slot.class)
+            .unwrap_or(0);
+        slot.class = TankClass::ALL[(current + step) % TankClass::ALL.len()];
+    }
+}
@@ -1,12 +1,13 @@
 use bevy::prelude::*;
+use bevy_rapier2d::prelude::Velocity;
 
-use crate::{
-    collider::{get_aabb_projection_radius, get_axes, get_obb_projection_radius, Collider},
-    walls::{Direction, Wall, WallType},
-    Velocity,
-};
+use crate::{collider::Collider, grid::WallGrid, walls::Wall};
 
-use super::{shooting::Bullet, tank::Tank};
+use super::{
+    handling::TankStats,
+    shooting::Bullet,
+    tank::{Remote, Tank},
+};
 
 #[derive(Component)]
 pub struct Static;
@@ -33,94 +34,370 @@ fn update_bounds_system(mut query: Query<(&Transform, &mut Collider), OnlyDynami
     }
 }
 
+/// A bullet ricocheted off a wall at `position`, `normal` is the surface it reflected about.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BulletBounced {
+    pub bullet: Entity,
+    pub position: Vec2,
+    pub normal: Vec2,
+}
+
 pub struct CollisionPlugin;
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                update_bounds_system,
-                bullet_wall_collision_system,
-                tank_wall_collision_system,
-            ),
-        );
+        app.init_resource::<WallGrid>()
+            .add_event::<BulletBounced>()
+            .add_systems(
+                Update,
+                (
+                    update_bounds_system,
+                    (
+                        bullet_wall_collision_system,
+                        tank_wall_collision_system,
+                        tank_tank_collision_system,
+                    ),
+                )
+                    .chain(),
+            );
         //.add_systems(PreUpdate, );
     }
 }
 
-fn reflect_angle(angle: f32, wall_type: WallType) -> f32 {
-    match wall_type {
-        WallType::Horizontal => -angle,
-        WallType::Vertical => 180.0 - angle,
+/// Reflects `velocity` about a surface with unit `normal`: `v - 2(v·n)n`.
+pub fn reflect(velocity: Vec2, normal: Vec2) -> Vec2 {
+    velocity - 2.0 * velocity.dot(normal) * normal
+}
+
+/// Follows a ray through `walls`, reflecting it off each wall it meets the
+/// way bullets ricochet. `margin` is the half size of the travelling object.
+/// Returns the start, every bounce point and the point where the path ends,
+/// which is the last wall hit once `max_bounces` is used up.
+pub fn trace_ricochet(
+    origin: Vec2,
+    direction: Vec2,
+    max_bounces: u32,
+    max_distance: f32,
+    walls: &[&Collider],
+    margin: Vec2,
+) -> Vec<Vec2> {
+    let mut points = vec![origin];
+    let mut origin = origin;
+    let mut direction = direction.normalize_or_zero();
+    let mut remaining = max_distance;
+
+    for bounce in 0..=max_bounces {
+        let hit = walls
+            .iter()
+            .filter_map(|wall| wall.ray_cast(origin, direction, margin))
+            .min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        let Some(hit) = hit.filter(|hit| hit.distance < remaining) else {
+            points.push(origin + direction * remaining);
+            break;
+        };
+
+        origin += direction * hit.distance;
+        remaining -= hit.distance;
+        points.push(origin);
+        if bounce == max_bounces {
+            break;
+        }
+
+        direction = reflect(direction, hit.normal);
+        // Step off the surface so the next cast doesn't hit the same face again
+        origin += hit.normal * 0.01;
+    }
+    points
+}
+
+/// Casts an instant beam: traces it through `walls` like `trace_ricochet` and
+/// cuts it at the first of `targets` it crosses. `shooter` is only skipped on
+/// the first segment, a reflected beam can hit it.
+/// Returns the beam's path and the target hit, if any.
+pub fn cast_beam(
+    origin: Vec2,
+    direction: Vec2,
+    max_bounces: u32,
+    range: f32,
+    walls: &[&Collider],
+    targets: &[(Entity, &Collider)],
+    shooter: Entity,
+) -> (Vec<Vec2>, Option<Entity>) {
+    let mut points = trace_ricochet(origin, direction, max_bounces, range, walls, Vec2::ZERO);
+
+    for index in 0..points.len() - 1 {
+        let (start, end) = (points[index], points[index + 1]);
+        let length = start.distance(end);
+        let direction = (end - start).normalize_or_zero();
+        let hit = targets
+            .iter()
+            .filter(|(target, _)| index > 0 || *target != shooter)
+            .filter_map(|(target, collider)| {
+                let ray_hit = collider.ray_cast(start, direction, Vec2::ZERO)?;
+                (ray_hit.distance <= length).then_some((*target, ray_hit.distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((target, distance)) = hit {
+            points.truncate(index + 1);
+            points.push(start + direction * distance);
+            return (points, Some(target));
+        }
+    }
+    (points, None)
+}
+
+/// Degrees between the firing angles `solve_bounce_shots` tries first.
+const SOLVER_STEP: f32 = 1.0;
+/// Halvings used to find the edges of a range of angles that hit.
+const SOLVER_REFINEMENTS: u32 = 6;
+
+/// A shot for `solve_bounce_shots` to find angles for.
+#[derive(Debug, Clone)]
+pub struct ShotProblem<'a> {
+    /// Centre of the shooting tank
+    pub shooter: Vec2,
+    /// How far from `shooter` shots leave the barrel
+    pub muzzle_offset: f32,
+    pub target: Vec2,
+    /// How close to `target` a shot has to pass to hit it
+    pub target_radius: f32,
+    /// Circles the shot must not cross on the way, such as teammates. The
+    /// shooter itself is always avoided once the shot has bounced.
+    pub avoid: &'a [(Vec2, f32)],
+    pub max_bounces: u32,
+    pub range: f32,
+    /// Half size of the bullet, as for `trace_ricochet`
+    pub margin: Vec2,
+}
+
+/// A firing angle that reaches the target.
+#[derive(Debug, Clone)]
+pub struct BounceShot {
+    /// Degrees
+    pub angle: f32,
+    pub bounces: u32,
+    /// Muzzle, bounce points and the point closest to the target
+    pub path: Vec<Vec2>,
+    /// Distance the bullet travels before it reaches the target
+    pub length: f32,
+}
+
+/// Distance along the segment `start`-`end` at which it first comes within
+/// `radius` of `center`.
+fn segment_circle_hit(start: Vec2, end: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let length = start.distance(end);
+    let direction = (end - start).normalize_or_zero();
+    let along = (center - start).dot(direction).clamp(0.0, length);
+    let off = (start + direction * along).distance(center);
+    if off > radius {
+        return None;
+    }
+    // Step back from the closest point to where the segment enters the circle
+    Some((along - (radius * radius - off * off).sqrt()).max(0.0))
+}
+
+/// Where a shot at `angle` goes, if it reaches the target.
+fn try_shot(problem: &ShotProblem, angle: f32, walls: &[&Collider]) -> Option<BounceShot> {
+    let direction = Vec2::from_angle(angle.to_radians());
+    let muzzle = problem.shooter + direction * problem.muzzle_offset;
+    let points = trace_ricochet(
+        muzzle,
+        direction,
+        problem.max_bounces,
+        problem.range,
+        walls,
+        problem.margin,
+    );
+
+    let mut length = 0.0;
+    for (index, segment) in points.windows(2).enumerate() {
+        let (start, end) = (segment[0], segment[1]);
+        let hit = segment_circle_hit(start, end, problem.target, problem.target_radius);
+        let blocked_at = problem
+            .avoid
+            .iter()
+            .copied()
+            .chain((index > 0).then_some((problem.shooter, problem.muzzle_offset)))
+            .filter_map(|(center, radius)| segment_circle_hit(start, end, center, radius))
+            .min_by(|a, b| a.total_cmp(b));
+
+        match (hit, blocked_at) {
+            (Some(hit), blocked) if blocked.is_none_or(|blocked| hit <= blocked) => {
+                let direction = (end - start).normalize_or_zero();
+                let mut path = points[..=index].to_vec();
+                path.push(start + direction * hit);
+                return Some(BounceShot {
+                    angle,
+                    bounces: index as u32,
+                    path,
+                    length: length + hit,
+                });
+            }
+            (_, Some(_)) => return None,
+            _ => length += start.distance(end),
+        }
+    }
+    None
+}
+
+/// Finds angles to fire at from `problem.shooter` whose ricochet path
+/// reaches `problem.target` within `problem.max_bounces` bounces, traced
+/// with `trace_ricochet` the way bullets fly. Every angle is tried in
+/// `SOLVER_STEP` steps; each run of neighbouring angles that hit is one
+/// shot, aimed at the middle of the run so small aiming errors still hit.
+/// Shots come back with the fewest bounces first, then the shortest.
+pub fn solve_bounce_shots(problem: &ShotProblem, walls: &[&Collider]) -> Vec<BounceShot> {
+    let to_target = problem.target - problem.shooter;
+    let direct = to_target.y.atan2(to_target.x).to_degrees();
+    let steps = (360.0 / SOLVER_STEP) as u32;
+    let hits: Vec<bool> = (0..steps)
+        .map(|step| try_shot(problem, direct + step as f32 * SOLVER_STEP, walls).is_some())
+        .collect();
+
+    // Runs of hitting steps, wrapping around past 360 degrees
+    let Some(start) = hits.iter().position(|hit| !hit) else {
+        return try_shot(problem, direct, walls).into_iter().collect();
+    };
+    let mut runs = Vec::new();
+    let mut run: Option<(u32, u32)> = None;
+    for offset in 1..=steps {
+        let step = (start as u32 + offset) % steps;
+        let unwrapped = start as u32 + offset;
+        match (hits[step as usize], &mut run) {
+            (true, Some((_, last))) => *last = unwrapped,
+            (true, None) => run = Some((unwrapped, unwrapped)),
+            (false, Some(_)) => runs.extend(run.take()),
+            (false, None) => {}
+        }
     }
+
+    let angle_at = |step: f32| direct + step * SOLVER_STEP;
+    // Binary search for the edge between a hitting and a missing angle
+    let refine = |hit: f32, miss: f32| {
+        let (mut hit, mut miss) = (hit, miss);
+        for _ in 0..SOLVER_REFINEMENTS {
+            let middle = (hit + miss) / 2.0;
+            if try_shot(problem, angle_at(middle), walls).is_some() {
+                hit = middle;
+            } else {
+                miss = middle;
+            }
+        }
+        hit
+    };
+
+    let mut shots: Vec<BounceShot> = runs
+        .into_iter()
+        .filter_map(|(first, last)| {
+            let low = refine(first as f32, first as f32 - 1.0);
+            let high = refine(last as f32, last as f32 + 1.0);
+            let middle = angle_at((low + high) / 2.0);
+            try_shot(problem, middle, walls)
+                .or_else(|| try_shot(problem, angle_at(first as f32), walls))
+                .map(|shot| BounceShot {
+                    angle: shot.angle.rem_euclid(360.0),
+                    ..shot
+                })
+        })
+        .collect();
+    shots.sort_by(|a, b| {
+        a.bounces
+            .cmp(&b.bounces)
+            .then(a.length.total_cmp(&b.length))
+    });
+    shots
 }
 
 fn bullet_wall_collision_system(
-    mut commands: Commands,
+    time: Res<Time>,
+    mut bounce_events: EventWriter<BulletBounced>,
     mut bullet_query: Query<(Entity, &Collider, &mut Bullet, &mut Transform)>,
-    wall_query: Query<(&Collider, &Wall)>,
+    wall_query: Query<&Collider, With<Wall>>,
+    wall_grid: Res<WallGrid>,
 ) {
     for (bullet_entity, bullet_collider, mut bullet, mut bullet_transform) in
         bullet_query.iter_mut()
     {
-        let mut collided = false;
+        if bullet.ghost {
+            continue;
+        }
+        bullet.wall_cooldown.tick(time.delta());
+        let velocity = bullet.velocity();
 
-        for (wall_collider, wall) in wall_query.iter() {
-            let wall_aabb = match wall_collider {
-                Collider::Obb(_) => {
-                    return;
-                }
-                Collider::Aabb(a) => a,
-            };
+        // Face normals are summed so that hitting the inside corner of two walls
+        // at once reflects off both; a wall tip is only used when no face was hit.
+        let mut face_normal = Vec2::ZERO;
+        let mut corner_normal = None;
+        let mut depth: f32 = 0.0;
+        let mut hit_wall = None;
 
-            let is_same_wall = match bullet.last_hit_wall {
-                Some(wall) => wall == wall_aabb.center.into(),
-                None => false,
+        for wall_entity in wall_grid.neighbours(bullet_collider.center()) {
+            let Ok(wall_collider) = wall_query.get(wall_entity) else {
+                continue;
             };
 
-            if is_same_wall {
+            let on_cooldown = !bullet.wall_cooldown.finished();
+            if on_cooldown && bullet.last_hit_wall == Some(wall_entity) {
                 continue;
             }
-            let collision = bullet_collider.collides_with(wall_collider);
-
-            if collision {
-                let new_angle = reflect_angle(bullet.angle, wall.wall_type.clone());
-                bullet.angle = new_angle;
-                bullet.last_hit_wall = Some(wall_aabb.center.into());
-
-                let normal = match wall.wall_type {
-                    WallType::Horizontal => {
-                        Vec2::new(0.0, if bullet.velocity().y > 0.0 { -1.0 } else { 1.0 })
-                    }
-                    WallType::Vertical => {
-                        Vec2::new(if bullet.velocity().x > 0.0 { -1.0 } else { 1.0 }, 0.0)
-                    }
-                };
-                let adjustment = normal * 0.1;
-                bullet_transform.translation.x += adjustment.x;
-                bullet_transform.translation.y += adjustment.y;
-
-                collided = true;
-                break;
+
+            let Some(contact) = bullet_collider.contact(wall_collider) else {
+                continue;
+            };
+            // Already moving away from this wall, nothing to reflect.
+            if velocity.dot(contact.normal) >= 0.0 {
+                continue;
             }
-        }
 
-        if collided {
-            bullet.bounce_count += 1;
-            if bullet.bounce_count > 5 {
-                commands.entity(bullet_entity).despawn();
+            if contact.corner {
+                corner_normal.get_or_insert(contact.normal);
+            } else {
+                face_normal += contact.normal;
+            }
+            if hit_wall.is_none() || contact.depth > depth {
+                depth = contact.depth;
+                hit_wall = Some(wall_entity);
             }
         }
+
+        let Some(normal) = face_normal.try_normalize().or(corner_normal) else {
+            continue;
+        };
+
+        let reflected = reflect(velocity, normal);
+        bullet.angle = reflected.y.atan2(reflected.x).to_degrees();
+        bullet.last_hit_wall = hit_wall;
+        bullet.wall_cooldown.reset();
+
+        let adjustment = normal * depth;
+        bullet_transform.translation.x += adjustment.x;
+        bullet_transform.translation.y += adjustment.y;
+
+        if hit_wall.is_some() {
+            bounce_events.send(BulletBounced {
+                bullet: bullet_entity,
+                position: bullet_transform.translation.xy(),
+                normal,
+            });
+        }
+
+        // Removal once out of bounces is left to the bullet's lifetime policy
+        bullet.bounce_count = bullet.bounce_count.saturating_add(1);
     }
 }
 fn tank_wall_collision_system(
     mut tank_query: Query<(&mut Velocity, &mut Transform, &Collider), With<Tank>>,
     wall_query: Query<(&Collider, &Wall)>,
+    wall_grid: Res<WallGrid>,
 ) {
     for (mut velocity, mut transform, tank_collider) in tank_query.iter_mut() {
-        for (wall_collider, wall) in wall_query.iter() {
+        for wall_entity in wall_grid.neighbours(tank_collider.center()) {
+            let Ok((wall_collider, wall)) = wall_query.get(wall_entity) else {
+                continue;
+            };
             if let Some((collision_normal, penetration_depth)) =
-                tank_collider.collision_info(wall_collider, Some(wall.wall_type.clone()))
+                tank_collider.collision_info(wall_collider, Some(wall.wall_type))
             {
                 // Adjust tank position to resolve the collision
                 let adjustment = collision_normal * penetration_depth;
@@ -128,14 +405,10 @@ fn tank_wall_collision_system(
                 transform.translation.y += adjustment.y * 1.;
 
                 // Adjust velocity to prevent moving into wall
-                let velocity_vec = Vec2::new(velocity.x, velocity.y);
-                let vn = velocity_vec.dot(collision_normal);
+                let vn = velocity.linvel.dot(collision_normal);
                 if vn < 0.0 {
                     // If moving into the wall, zero out the component along the normal
-                    let velocity_adjustment = collision_normal * vn;
-                    let new_velocity = velocity_vec - velocity_adjustment;
-                    velocity.x = new_velocity.x;
-                    velocity.y = new_velocity.y;
+                    velocity.linvel -= collision_normal * vn;
                 }
                 // Break after handling the collision with one wall
                 break;
@@ -143,3 +416,114 @@ fn tank_wall_collision_system(
         }
     }
 }
+
+/// Pushes overlapping tanks apart. Online each client only moves the tanks it
+/// drives and reports where they end up; the other side of the contact takes
+/// its own share of the push on its own client.
+fn tank_tank_collision_system(
+    stats_assets: Res<Assets<TankStats>>,
+    mut tank_query: Query<(&Tank, &mut Transform, &Collider, Has<Remote>)>,
+) {
+    let mut combinations = tank_query.iter_combinations_mut();
+    while let Some([first, second]) = combinations.fetch_next() {
+        let (tank_1, mut transform_1, collider_1, remote_1) = first;
+        let (tank_2, mut transform_2, collider_2, remote_2) = second;
+        let Some(contact) = collider_1.contact(collider_2) else {
+            continue;
+        };
+
+        // Split the separation by mass so a heavy tank shoves a light one aside.
+        let mass_1 = tank_1.stats(&stats_assets).mass;
+        let mass_2 = tank_2.stats(&stats_assets).mass;
+        let total_mass = mass_1 + mass_2;
+        if !remote_1 {
+            let push_1 = contact.normal * contact.depth * (mass_2 / total_mass);
+            transform_1.translation.x += push_1.x;
+            transform_1.translation.y += push_1.y;
+        }
+        if !remote_2 {
+            let push_2 = -contact.normal * contact.depth * (mass_1 / total_mass);
+            transform_2.translation.x += push_2.x;
+            transform_2.translation.y += push_2.y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::primitives::Aabb;
+
+    use super::*;
+
+    fn boxed(min: Vec2, max: Vec2) -> Collider {
+        Collider::Aabb(Aabb::from_min_max(min.extend(0.0), max.extend(0.0)))
+    }
+
+    #[test]
+    fn reflect_off_axis_aligned_walls() {
+        assert_eq!(reflect(Vec2::new(3.0, -4.0), Vec2::Y), Vec2::new(3.0, 4.0));
+        assert_eq!(reflect(Vec2::new(-2.0, 1.0), Vec2::X), Vec2::new(2.0, 1.0));
+        // Moving along the wall leaves the velocity alone
+        assert_eq!(reflect(Vec2::new(5.0, 0.0), Vec2::Y), Vec2::new(5.0, 0.0));
+    }
+
+    #[test]
+    fn reflect_off_a_wall_corner() {
+        let wall = boxed(Vec2::new(-10.0, -10.0), Vec2::new(10.0, 10.0));
+        // Overlaps the top right tip, centred past both faces
+        let bullet = boxed(Vec2::new(8.0, 8.0), Vec2::new(14.0, 14.0));
+        let contact = bullet.contact(&wall).unwrap();
+        assert!(contact.corner);
+
+        // Flying straight into the tip sends the bullet back the way it came
+        let velocity = Vec2::new(-1.0, -1.0);
+        assert!(reflect(velocity, contact.normal).abs_diff_eq(-velocity, 1e-5));
+        // A glancing hit keeps the speed but turns away from the wall
+        let glancing = reflect(Vec2::new(-1.0, 0.0), contact.normal);
+        assert!(glancing.abs_diff_eq(Vec2::new(0.0, 1.0), 1e-5));
+    }
+
+    /// The target sits straight above the shooter behind a wall, with a
+    /// mirror wall off to the right to bank a shot off.
+    fn one_bounce_arena() -> Vec<Collider> {
+        vec![
+            boxed(Vec2::new(-50.0, 100.0), Vec2::new(50.0, 110.0)),
+            boxed(Vec2::new(100.0, -200.0), Vec2::new(110.0, 300.0)),
+        ]
+    }
+
+    fn problem(max_bounces: u32) -> ShotProblem<'static> {
+        ShotProblem {
+            shooter: Vec2::ZERO,
+            muzzle_offset: 20.0,
+            target: Vec2::new(0.0, 200.0),
+            target_radius: 15.0,
+            avoid: &[],
+            max_bounces,
+            range: 1000.0,
+            margin: Vec2::splat(2.5),
+        }
+    }
+
+    #[test]
+    fn solver_finds_a_one_bounce_shot() {
+        let arena = one_bounce_arena();
+        let walls: Vec<&Collider> = arena.iter().collect();
+        let shots = solve_bounce_shots(&problem(1), &walls);
+
+        let shot = shots.first().expect("the mirror shot should be found");
+        assert_eq!(shot.bounces, 1);
+        // Aimed at the target mirrored across the face the bullet centre bounces at
+        let mirrored = Vec2::new(2.0 * 97.5, 200.0);
+        let expected = mirrored.y.atan2(mirrored.x).to_degrees();
+        assert!((shot.angle.rem_euclid(360.0) - expected).abs() < 3.0);
+        assert!(shots.iter().all(|shot| shot.bounces <= 1));
+    }
+
+    #[test]
+    fn solver_ignores_shots_past_the_bounce_limit() {
+        let arena = one_bounce_arena();
+        let walls: Vec<&Collider> = arena.iter().collect();
+        assert!(solve_bounce_shots(&problem(0), &walls).is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+
+use super::{
+    modes::{Flag, Team},
+    shooting::Bullet,
+    tank::{Tank, Turret},
+};
+
+/// Colours to tell players apart by, as many as on the server.
+pub const PALETTE_SIZE: u8 = 8;
+/// How much darker a turret is than its player's colour, so it stands out
+/// on a hull of the same colour.
+const TURRET_SHADE: f32 = 0.15;
+const PALETTE_KEY: KeyCode = KeyCode::F5;
+
+const STANDARD: [(&str, Color); PALETTE_SIZE as usize] = [
+    ("Red", Color::srgb(0.9, 0.25, 0.2)),
+    ("Blue", Color::srgb(0.2, 0.45, 0.95)),
+    ("Green", Color::srgb(0.3, 0.8, 0.3)),
+    ("Yellow", Color::srgb(0.95, 0.85, 0.2)),
+    ("Purple", Color::srgb(0.65, 0.35, 0.9)),
+    ("Orange", Color::srgb(1.0, 0.55, 0.1)),
+    ("Cyan", Color::srgb(0.2, 0.85, 0.9)),
+    ("Pink", Color::srgb(0.95, 0.45, 0.75)),
+];
+
+/// Okabe and Ito's palette, told apart with every common colour blindness.
+/// Their black is swapped for grey, which shows on the dark background.
+const COLOR_BLIND: [(&str, Color); PALETTE_SIZE as usize] = [
+    ("Vermillion", Color::srgb(0.835, 0.369, 0.)),
+    ("Blue", Color::srgb(0., 0.447, 0.698)),
+    ("Green", Color::srgb(0., 0.62, 0.451)),
+    ("Yellow", Color::srgb(0.941, 0.894, 0.259)),
+    ("Purple", Color::srgb(0.8, 0.475, 0.655)),
+    ("Orange", Color::srgb(0.902, 0.624, 0.)),
+    ("Sky blue", Color::srgb(0.337, 0.706, 0.914)),
+    ("Grey", Color::srgb(0.733, 0.733, 0.733)),
+];
+
+/// The colours players, their bullets and the teams are drawn in.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Palette {
+    #[default]
+    Standard,
+    ColorBlind,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 2] = [Palette::Standard, Palette::ColorBlind];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Standard => "standard",
+            Palette::ColorBlind => "colour-blind",
+        }
+    }
+
+    fn colors(&self) -> &'static [(&'static str, Color); PALETTE_SIZE as usize] {
+        match self {
+            Palette::Standard => &STANDARD,
+            Palette::ColorBlind => &COLOR_BLIND,
+        }
+    }
+
+    pub fn color(&self, color: PlayerColor) -> Color {
+        self.colors()[(color.0 % PALETTE_SIZE) as usize].1
+    }
+
+    pub fn color_name(&self, color: PlayerColor) -> &'static str {
+        self.colors()[(color.0 % PALETTE_SIZE) as usize].0
+    }
+
+    /// Teams take the palette's first two colours, far apart in all of them.
+    pub fn team_color(&self, team: Team) -> Color {
+        match team {
+            Team::Red => self.color(PlayerColor(0)),
+            Team::Blue => self.color(PlayerColor(1)),
+        }
+    }
+}
+
+/// Index into the `Palette` of the player driving a tank. Local players go
+/// by their slot, online the server hands them out.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerColor(pub u8);
+
+pub struct ColorsPlugin;
+impl Plugin for ColorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Palette>().add_systems(
+            Update,
+            (
+                cycle_palette_system,
+                tint_tanks_system,
+                tint_bullets_system,
+                tint_flags_system,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// F5 switches to the next palette.
+fn cycle_palette_system(keys: Res<ButtonInput<KeyCode>>, mut palette: ResMut<Palette>) {
+    if keys.just_pressed(PALETTE_KEY) {
+        let current = Palette::ALL
+            .iter()
+            .position(|other| other == &*palette)
+            .unwrap_or(0);
+        *palette = Palette::ALL[(current + 1) % Palette::ALL.len()];
+    }
+}
+
+/// What `tint_tanks_system` needs to know about a tank.
+type Tinted<'a> = (
+    Ref<'a, PlayerColor>,
+    Option<Ref<'a, Team>>,
+    &'a mut Sprite,
+    &'a Children,
+);
+
+/// Hulls take their team's colour in the team modes and the player's
+/// otherwise. Turrets always show the player's.
+fn tint_tanks_system(
+    palette: Res<Palette>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut tank_query: Query<Tinted>,
+    turret_query: Query<&MeshMaterial2d<ColorMaterial>, With<Turret>>,
+) {
+    for (color, team, mut sprite, children) in tank_query.iter_mut() {
+        let team_changed = team.as_ref().is_some_and(|team| team.is_changed());
+        if !palette.is_changed() && !color.is_changed() && !team_changed {
+            continue;
+        }
+        sprite.color = team.map_or(palette.color(*color), |team| palette.team_color(*team));
+        // The barrel shares the turret's material
+        for child in children.iter() {
+            let Ok(material) = turret_query.get(*child) else {
+                continue;
+            };
+            if let Some(material) = materials.get_mut(&material.0) {
+                material.color = palette.color(*color).darker(TURRET_SHADE);
+            }
+        }
+    }
+}
+
+/// Bullets come out in their tank's hull colour.
+fn tint_bullets_system(
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    bullet_query: Query<(&Bullet, &MeshMaterial2d<ColorMaterial>), Added<Bullet>>,
+    tank_query: Query<&Sprite, With<Tank>>,
+) {
+    for (bullet, material) in bullet_query.iter() {
+        let Ok(sprite) = tank_query.get(bullet.owner) else {
+            continue;
+        };
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color = sprite.color;
+        }
+    }
+}
+
+/// Flags are spawned in their team's colour, this only follows palette changes.
+fn tint_flags_system(
+    palette: Res<Palette>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    flag_query: Query<(&Flag, &MeshMaterial2d<ColorMaterial>)>,
+) {
+    if !palette.is_changed() {
+        return;
+    }
+    for (flag, material) in flag_query.iter() {
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color = palette.team_color(flag.team);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+use crate::collider::Collider;
+
+use super::{
+    modes::{ModeRules, Team},
+    pickups::{PowerUp, PowerUps},
+    players::Player,
+    shooting::Bullet,
+    tank::Tank,
+};
+
+#[derive(Component, Debug, Clone)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// `amount` of health to take from `target`. `source` is the tank responsible.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Damage {
+    pub target: Entity,
+    pub source: Entity,
+    pub amount: f32,
+}
+
+/// Sent when damage takes a tank's health down to zero. `killer` is the
+/// tank that fired the shot, which may be the victim itself. The players
+/// driving both are copied in since the victim is gone by the time this is read.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TankDestroyed {
+    pub tank: Entity,
+    pub killer: Entity,
+    pub victim_player: Option<Player>,
+    pub killer_player: Option<Player>,
+}
+
+pub struct CombatPlugin;
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>()
+            .add_event::<TankDestroyed>()
+            .add_systems(
+                Update,
+                (bullet_tank_collision_system, damage_system).chain(),
+            );
+    }
+}
+
+fn bullet_tank_collision_system(
+    mut commands: Commands,
+    mut bullet_query: Query<(Entity, &Collider, &mut Bullet)>,
+    // Tanks played elsewhere carry no `Health` but still stop the bullet
+    tank_query: Query<(Entity, &Collider, Option<&Health>), With<Tank>>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    for (bullet_entity, bullet_collider, mut bullet) in bullet_query.iter_mut() {
+        // A fresh bullet starts inside its shooter's muzzle area, so it can only
+        // hurt the shooter once it has cleared the hull or bounced off something.
+        if !bullet.left_owner {
+            let inside_owner = tank_query
+                .get(bullet.owner)
+                .is_ok_and(|(_, owner_collider, _)| bullet_collider.collides_with(owner_collider));
+            bullet.left_owner = bullet.bounce_count > 0 || !inside_owner;
+        }
+
+        for (tank_entity, tank_collider, health) in tank_query.iter() {
+            if health.is_some_and(Health::is_dead)
+                || (tank_entity == bullet.owner && !bullet.left_owner)
+            {
+                continue;
+            }
+            if !bullet_collider.collides_with(tank_collider) {
+                continue;
+            }
+
+            commands.entity(bullet_entity).try_despawn();
+            damage_events.send(Damage {
+                target: tank_entity,
+                source: bullet.owner,
+                amount: bullet.damage,
+            });
+            break;
+        }
+    }
+}
+
+pub fn damage_system(
+    mut commands: Commands,
+    mut damage_events: EventReader<Damage>,
+    mut tank_query: Query<(&mut Health, Option<&PowerUps>)>,
+    (player_query, team_query): (Query<&Player>, Query<&Team>),
+    mode_rules: Res<ModeRules>,
+    mut destroyed_events: EventWriter<TankDestroyed>,
+) {
+    for damage in damage_events.read() {
+        let Ok((mut health, power_ups)) = tank_query.get_mut(damage.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+        let target_team = team_query.get(damage.target).ok().copied();
+        let source_team = team_query.get(damage.source).ok().copied();
+        if damage.source != damage.target && !mode_rules.can_damage(source_team, target_team) {
+            continue;
+        }
+        if power_ups.is_some_and(|power_ups| power_ups.has(PowerUp::Shield)) {
+            continue;
+        }
+
+        health.current -= damage.amount;
+        if health.is_dead() {
+            destroyed_events.send(TankDestroyed {
+                tank: damage.target,
+                killer: damage.source,
+                victim_player: player_query.get(damage.target).ok().copied(),
+                killer_player: player_query.get(damage.source).ok().copied(),
+            });
+            commands.entity(damage.target).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use bevy::{color::palettes::css, prelude::*};
+
+use crate::{
+    collider::Collider,
+    constants::{
+        BULLET_OFFSET, GAME_FIELD_HEIGHT, GAME_FIELD_WIDTH, GRID_CELL_HORIZONTAL_AMOUNT,
+        GRID_CELL_SIZE, GRID_CELL_VERTICAL_AMOUNT,
+    },
+    walls::Wall,
+};
+
+use super::{
+    bots::Bot,
+    collision::{solve_bounce_shots, trace_ricochet, BounceShot, BulletBounced, ShotProblem},
+    modes::Team,
+    shooting::{Bullet, BulletLifetime},
+    tank::Tank,
+    weapons::{Armory, EquippedWeapon, WeaponDef},
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+/// Seconds a contact normal stays on screen after a bounce.
+const CONTACT_LIFETIME: f32 = 1.0;
+const CONTACT_NORMAL_LENGTH: f32 = 20.0;
+/// Cap on predicted paths of bullets with no age or bounce limit.
+const TRAJECTORY_LENGTH: f32 = 2000.0;
+const TRAJECTORY_BOUNCES: u32 = 20;
+const TRAINING_KEY: KeyCode = KeyCode::F4;
+/// Most bounces training mode looks for, solving gets slow past a few.
+const TRAINING_BOUNCES: u32 = 3;
+/// Seconds between training mode solving its shots again.
+const TRAINING_REFRESH: f32 = 0.25;
+
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+/// Shows every player the bounce shots that would reach the tanks they are up against.
+#[derive(Resource, Default)]
+pub struct TrainingMode {
+    pub enabled: bool,
+}
+
+pub struct DebugOverlayPlugin;
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .init_resource::<TrainingMode>()
+            .add_systems(
+                Update,
+                (
+                    toggle_overlay_system,
+                    (
+                        draw_grid_system,
+                        draw_colliders_system,
+                        draw_contacts_system,
+                        draw_trajectories_system,
+                    )
+                        .run_if(overlay_enabled),
+                    draw_bounce_shots_system.run_if(|training: Res<TrainingMode>| training.enabled),
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn overlay_enabled(overlay: Res<DebugOverlay>) -> bool {
+    overlay.enabled
+}
+
+fn toggle_overlay_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut training: ResMut<TrainingMode>,
+) {
+    if keys.just_pressed(TOGGLE_KEY) {
+        overlay.enabled = !overlay.enabled;
+    }
+    if keys.just_pressed(TRAINING_KEY) {
+        training.enabled = !training.enabled;
+    }
+}
+
+fn draw_grid_system(mut gizmos: Gizmos) {
+    let left = -GAME_FIELD_WIDTH / 2.0;
+    let bottom = -GAME_FIELD_HEIGHT / 2.0;
+    let color = css::DARK_SLATE_GRAY;
+
+    for x in 0..=GRID_CELL_HORIZONTAL_AMOUNT {
+        let x = left + x as f32 * GRID_CELL_SIZE;
+        gizmos.line_2d(Vec2::new(x, bottom), Vec2::new(x, -bottom), color);
+    }
+    for y in 0..=GRID_CELL_VERTICAL_AMOUNT {
+        let y = bottom + y as f32 * GRID_CELL_SIZE;
+        gizmos.line_2d(Vec2::new(left, y), Vec2::new(-left, y), color);
+    }
+}
+
+fn draw_colliders_system(mut gizmos: Gizmos, query: Query<&Collider>) {
+    for collider in query.iter() {
+        let (isometry, color) = match collider {
+            Collider::Aabb(aabb) => (Isometry2d::from_translation(aabb.center.xy()), css::LIME),
+            Collider::Obb(obb) => (
+                Isometry2d::new(obb.center, Rot2::degrees(obb.rotation)),
+                css::YELLOW,
+            ),
+        };
+        gizmos.rect_2d(isometry, collider.half_extents() * 2.0, color);
+    }
+}
+
+fn draw_contacts_system(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    mut bounce_events: EventReader<BulletBounced>,
+    mut contacts: Local<Vec<(BulletBounced, Timer)>>,
+) {
+    for event in bounce_events.read() {
+        contacts.push((
+            *event,
+            Timer::from_seconds(CONTACT_LIFETIME, TimerMode::Once),
+        ));
+    }
+    contacts.retain_mut(|(_, timer)| !timer.tick(time.delta()).finished());
+
+    for (contact, timer) in contacts.iter() {
+        let color = Color::from(css::RED).with_alpha(timer.fraction_remaining());
+        gizmos.arrow_2d(
+            contact.position,
+            contact.position + contact.normal * CONTACT_NORMAL_LENGTH,
+            color,
+        );
+    }
+}
+
+fn draw_trajectories_system(
+    mut gizmos: Gizmos,
+    bullet_query: Query<(&Bullet, &Transform, &Collider)>,
+    wall_query: Query<&Collider, With<Wall>>,
+) {
+    let walls: Vec<&Collider> = wall_query.iter().collect();
+
+    for (bullet, transform, collider) in bullet_query.iter() {
+        let lifetime = bullet.lifetime;
+        let remaining_bounces = lifetime.max_bounces.map_or(TRAJECTORY_BOUNCES, |max| {
+            max.saturating_sub(bullet.bounce_count) as u32
+        });
+        let remaining_distance = lifetime.max_age.map_or(TRAJECTORY_LENGTH, |max| {
+            (max - bullet.age).max(0.0) * bullet.speed
+        });
+        let path = trace_ricochet(
+            transform.translation.xy(),
+            bullet.velocity(),
+            remaining_bounces,
+            remaining_distance,
+            &walls,
+            collider.half_extents(),
+        );
+        gizmos.linestrip_2d(path.iter().copied(), css::ORANGE);
+        for point in &path[1..path.len() - 1] {
+            gizmos.circle_2d(Isometry2d::from_translation(*point), 3.0, css::ORANGE);
+        }
+    }
+}
+
+/// Training mode's last solutions, each marked if it is the best for its target.
+struct SolvedShots {
+    shots: Vec<(BounceShot, bool)>,
+    refresh: Timer,
+}
+
+impl Default for SolvedShots {
+    fn default() -> Self {
+        Self {
+            shots: Vec::new(),
+            refresh: Timer::from_seconds(TRAINING_REFRESH, TimerMode::Repeating),
+        }
+    }
+}
+
+fn bounce_color(bounces: u32) -> Srgba {
+    [css::LIME, css::YELLOW, css::ORANGE, css::RED][(bounces as usize).min(3)]
+}
+
+/// Solves the bounce shots from every player's tank to each enemy tank a few
+/// times a second and draws them, the best one for each target brighter.
+fn draw_bounce_shots_system(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    (armory, weapon_assets, lifetime): (Res<Armory>, Res<Assets<WeaponDef>>, Res<BulletLifetime>),
+    shooter_query: Query<(Entity, &Transform, &EquippedWeapon, Option<&Team>), Without<Bot>>,
+    tank_query: Query<(Entity, &Transform, &Collider, Option<&Team>), With<Tank>>,
+    wall_query: Query<&Collider, With<Wall>>,
+    mut solved: Local<SolvedShots>,
+) {
+    let SolvedShots { shots, refresh } = &mut *solved;
+    if refresh.tick(time.delta()).just_finished() {
+        let walls: Vec<&Collider> = wall_query.iter().collect();
+        shots.clear();
+        for (shooter, transform, weapon, team) in shooter_query.iter() {
+            let Some(def) = armory.get(weapon.0, &weapon_assets) else {
+                continue;
+            };
+            let lifetime = def.lifetime(*lifetime);
+            for (target, target_transform, target_collider, target_team) in tank_query.iter() {
+                if target == shooter || (team.is_some() && team == target_team) {
+                    continue;
+                }
+                let problem = ShotProblem {
+                    shooter: transform.translation.xy(),
+                    muzzle_offset: BULLET_OFFSET,
+                    target: target_transform.translation.xy(),
+                    target_radius: target_collider.half_extents().min_element(),
+                    avoid: &[],
+                    max_bounces: lifetime
+                        .max_bounces
+                        .map_or(TRAINING_BOUNCES, |max| TRAINING_BOUNCES.min(max as u32)),
+                    range: lifetime
+                        .max_age
+                        .map_or(TRAJECTORY_LENGTH, |max_age| max_age * def.speed),
+                    margin: Vec2::splat(def.size / 2.),
+                };
+                // Solutions come best first
+                let solved = solve_bounce_shots(&problem, &walls);
+                shots.extend(
+                    solved
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, shot)| (shot, i == 0)),
+                );
+            }
+        }
+    }
+
+    for (shot, best) in shots.iter() {
+        let alpha = if *best { 0.9 } else { 0.3 };
+        let color = Color::from(bounce_color(shot.bounces)).with_alpha(alpha);
+        gizmos.linestrip_2d(shot.path.iter().copied(), color);
+    }
+}
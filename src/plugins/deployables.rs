@@ -0,0 +1,348 @@
+use bevy::{color::palettes::css, prelude::*, render::primitives::Aabb};
+use bevy_rapier2d::prelude::{Collider as RapierCollider, RigidBody};
+pub use shared::deployables::DeployableKind;
+
+use crate::{
+    collider::Collider,
+    constants::GRID_CELL_SIZE,
+    grid::WallGrid,
+    walls::{Wall, WallType},
+};
+
+use super::{
+    collision::Static,
+    combat::Damage,
+    input::TankInput,
+    pickups::{PowerUp, PowerUps},
+    tank::Tank,
+};
+
+const MINE_SIZE: f32 = 8.;
+const MINE_DAMAGE: f32 = 1.;
+/// Mines a single tank can have in the maze at once.
+const MAX_MINES: usize = 3;
+/// Seconds after being dropped before a mine goes off on contact.
+const MINE_ARM_DELAY: f32 = 1.5;
+/// Opponents see a mine only once they are this close to it.
+const MINE_REVEAL_RADIUS: f32 = 80.;
+/// Tanks this close to an exploding mine are hit by it.
+const MINE_BLAST_RADIUS: f32 = 40.;
+
+const BARRIER_LENGTH: f32 = GRID_CELL_SIZE / 2.;
+const BARRIER_THICKNESS: f32 = 5.;
+/// Seconds a barrier stands before crumbling.
+const BARRIER_LIFETIME: f32 = 8.;
+/// Barriers a single tank can have standing at once.
+const MAX_BARRIERS: usize = 1;
+/// Gap between the front of the hull and a barrier dropped by it.
+const BARRIER_GAP: f32 = 15.;
+
+#[derive(Component, Debug)]
+pub struct Mine {
+    /// Tank that laid the mine, it can drive over it safely.
+    pub owner: Entity,
+    pub damage: f32,
+    pub arming: Timer,
+    /// The server's id for it, `None` offline
+    pub id: Option<u32>,
+}
+
+impl Mine {
+    pub fn new(owner: Entity) -> Self {
+        Self {
+            owner,
+            damage: MINE_DAMAGE,
+            arming: Timer::from_seconds(MINE_ARM_DELAY, TimerMode::Once),
+            id: None,
+        }
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.arming.finished()
+    }
+}
+
+/// A temporary wall a tank puts down in front of itself. Barriers are `Wall`s
+/// too, so bullets and lasers bounce off them like off the maze.
+#[derive(Component, Debug)]
+pub struct Barrier {
+    pub owner: Entity,
+    pub lifetime: Timer,
+    /// The server's id for it, `None` offline
+    pub id: Option<u32>,
+}
+
+impl Barrier {
+    pub fn new(owner: Entity) -> Self {
+        Self {
+            owner,
+            lifetime: Timer::from_seconds(BARRIER_LIFETIME, TimerMode::Once),
+            id: None,
+        }
+    }
+}
+
+/// How mines and barriers get into the maze.
+#[derive(Resource, Debug, Clone)]
+pub struct DeployableRules {
+    /// Off when they only go in once the server has placed them.
+    pub spawn_locally: bool,
+}
+
+impl Default for DeployableRules {
+    fn default() -> Self {
+        Self {
+            spawn_locally: true,
+        }
+    }
+}
+
+/// Sent when a tank drops a mine or a barrier, so the network layer can report it.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Deployed {
+    pub owner: Entity,
+    pub kind: DeployableKind,
+    pub position: Vec2,
+    pub wall_type: Option<WallType>,
+}
+
+/// Sent when a mine goes off, with the server's id for it if it has one.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MineDetonated {
+    pub id: Option<u32>,
+}
+
+pub struct DeployablesPlugin;
+impl Plugin for DeployablesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DeployableRules>()
+            .add_event::<Deployed>()
+            .add_event::<MineDetonated>()
+            .add_systems(
+                Update,
+                (
+                    lay_mine_system,
+                    place_barrier_system,
+                    mine_arming_system,
+                    mine_trigger_system,
+                    mine_visibility_system,
+                    barrier_lifetime_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn lay_mine_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    tank_query: Query<(Entity, &TankInput, &Transform, &PowerUps), With<Tank>>,
+    (mine_query, rules): (Query<&Mine>, Res<DeployableRules>),
+    mut deployed_events: EventWriter<Deployed>,
+) {
+    for (tank, input, transform, power_ups) in tank_query.iter() {
+        if !input.lay_mine || !power_ups.has(PowerUp::MineLayer) {
+            continue;
+        }
+        if mine_query.iter().filter(|mine| mine.owner == tank).count() >= MAX_MINES {
+            continue;
+        }
+
+        let position = transform.translation.xy();
+        if rules.spawn_locally {
+            let mine = Mine::new(tank);
+            spawn_mine(&mut commands, &mut meshes, &mut materials, mine, position);
+        }
+        deployed_events.send(Deployed {
+            owner: tank,
+            kind: DeployableKind::Mine,
+            position,
+            wall_type: None,
+        });
+    }
+}
+
+pub fn spawn_mine(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    mine: Mine,
+    position: Vec2,
+) -> Entity {
+    let aabb = Aabb {
+        center: position.extend(0.).into(),
+        half_extents: Vec3::new(MINE_SIZE, MINE_SIZE, 0.).into(),
+    };
+    commands
+        .spawn((
+            Mesh2d(meshes.add(Circle::new(MINE_SIZE))),
+            MeshMaterial2d(materials.add(Color::from(css::DARK_RED))),
+        ))
+        .insert(Transform::from_xyz(position.x, position.y, -0.5))
+        .insert(mine)
+        .insert(Collider::Aabb(aabb))
+        .id()
+}
+
+fn place_barrier_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut wall_grid: ResMut<WallGrid>,
+    tank_query: Query<(Entity, &TankInput, &Tank, &Transform)>,
+    (barrier_query, rules): (Query<&Barrier>, Res<DeployableRules>),
+    mut deployed_events: EventWriter<Deployed>,
+) {
+    for (tank, input, hull, transform) in tank_query.iter() {
+        if !input.place_barrier {
+            continue;
+        }
+        let placed = barrier_query
+            .iter()
+            .filter(|barrier| barrier.owner == tank)
+            .count();
+        if placed >= MAX_BARRIERS {
+            continue;
+        }
+
+        // Barriers are kept axis aligned like the maze walls, across whichever
+        // axis the tank is mostly facing along.
+        let facing = Vec2::from_angle(transform.rotation.to_euler(EulerRot::XYZ).2);
+        let wall_type = if facing.x.abs() > facing.y.abs() {
+            WallType::Vertical
+        } else {
+            WallType::Horizontal
+        };
+        let position = transform.translation.xy() + facing * (hull.half_extents().x + BARRIER_GAP);
+
+        if rules.spawn_locally {
+            spawn_barrier(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut wall_grid,
+                Barrier::new(tank),
+                position,
+                wall_type,
+            );
+        }
+        deployed_events.send(Deployed {
+            owner: tank,
+            kind: DeployableKind::Barrier,
+            position,
+            wall_type: Some(wall_type),
+        });
+    }
+}
+
+pub fn spawn_barrier(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    wall_grid: &mut WallGrid,
+    barrier: Barrier,
+    position: Vec2,
+    wall_type: WallType,
+) -> Entity {
+    let half_extents = match wall_type {
+        WallType::Horizontal => Vec2::new(BARRIER_LENGTH, BARRIER_THICKNESS) / 2.,
+        WallType::Vertical => Vec2::new(BARRIER_THICKNESS, BARRIER_LENGTH) / 2.,
+    };
+    let aabb = Aabb {
+        center: position.extend(0.).into(),
+        half_extents: half_extents.extend(0.).into(),
+    };
+    let barrier = commands
+        .spawn((
+            Mesh2d(meshes.add(Rectangle::from_size(half_extents * 2.))),
+            MeshMaterial2d(materials.add(Color::from(css::SANDY_BROWN))),
+            RigidBody::Fixed,
+        ))
+        .insert(Transform::from_xyz(position.x, position.y, 0.))
+        .insert(Wall { wall_type })
+        .insert(barrier)
+        .insert(Static)
+        .insert(RapierCollider::cuboid(half_extents.x, half_extents.y))
+        .insert(Collider::Aabb(aabb))
+        .id();
+
+    wall_grid.insert_at(position, barrier);
+    barrier
+}
+
+fn mine_arming_system(time: Res<Time>, mut query: Query<&mut Mine>) {
+    for mut mine in query.iter_mut() {
+        mine.arming.tick(time.delta());
+    }
+}
+
+fn mine_trigger_system(
+    mut commands: Commands,
+    mine_query: Query<(Entity, &Mine, &Collider, &Transform)>,
+    tank_query: Query<(Entity, &Collider, &Transform), With<Tank>>,
+    mut damage_events: EventWriter<Damage>,
+    mut detonated_events: EventWriter<MineDetonated>,
+) {
+    for (mine_entity, mine, mine_collider, mine_transform) in mine_query.iter() {
+        if !mine.is_armed() {
+            continue;
+        }
+        let triggered = tank_query.iter().any(|(tank, tank_collider, _)| {
+            tank != mine.owner && mine_collider.collides_with(tank_collider)
+        });
+        if !triggered {
+            continue;
+        }
+
+        // The blast hurts everyone close by, the layer included
+        let position = mine_transform.translation.xy();
+        for (tank, _, tank_transform) in tank_query.iter() {
+            if tank_transform.translation.xy().distance(position) <= MINE_BLAST_RADIUS {
+                damage_events.send(Damage {
+                    target: tank,
+                    source: mine.owner,
+                    amount: mine.damage,
+                });
+            }
+        }
+        commands.entity(mine_entity).despawn();
+        detonated_events.send(MineDetonated { id: mine.id });
+    }
+}
+
+/// Players share one screen, so a mine can't be hidden from some of them
+/// only. It shows while arming, so its layer sees where it went, and after
+/// that only while a tank other than its layer is close enough to spot it.
+fn mine_visibility_system(
+    tank_query: Query<(Entity, &Transform), With<Tank>>,
+    mut mine_query: Query<(&Mine, &Transform, &mut Visibility)>,
+) {
+    for (mine, transform, mut visibility) in mine_query.iter_mut() {
+        let position = transform.translation.xy();
+        let spotted = !mine.is_armed()
+            || tank_query.iter().any(|(tank, tank_transform)| {
+                tank != mine.owner
+                    && tank_transform.translation.xy().distance(position) <= MINE_REVEAL_RADIUS
+            });
+        visibility.set_if_neq(if spotted {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+fn barrier_lifetime_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut wall_grid: ResMut<WallGrid>,
+    mut query: Query<(Entity, &mut Barrier)>,
+) {
+    for (entity, mut barrier) in query.iter_mut() {
+        if barrier.lifetime.tick(time.delta()).finished() {
+            wall_grid.remove(entity);
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::walls::{setup_walls, Maze, MazeSeed, Wall};
+
+use super::{
+    bindings::Bindings,
+    colors::{Palette, PlayerColor},
+    deployables::Mine,
+    input::Controls,
+    modes::{Flag, FlagCaptured, ModeRounds, ModeRules, RoundEnd, Team},
+    pickups::Pickup,
+    players::{LocalPlayers, Player},
+    scoring::Scoreboard,
+    shooting::Bullet,
+    tank::Tank,
+};
+
+/// Where the game is, from the title screen through a match of several rounds.
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    /// Players join and pick their classes
+    Lobby,
+    /// A fresh maze with the tanks in place, waiting for the go
+    Countdown,
+    InRound,
+    /// One tank or none left, showing who took the round
+    RoundOver,
+    MatchOver,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct MatchRules {
+    /// Round wins that take the match
+    pub rounds_to_win: u32,
+    /// Seconds between the maze appearing and the round starting
+    pub countdown: f32,
+    /// Seconds the round result stays up before the next round
+    pub round_over: f32,
+    /// `false` when the server decides when rounds start and end
+    pub driven_locally: bool,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            rounds_to_win: 3,
+            countdown: 3.,
+            round_over: 3.,
+            driven_locally: true,
+        }
+    }
+}
+
+/// How far the current match has got. Round wins are kept on the `Scoreboard`.
+#[derive(Resource, Debug, Default)]
+pub struct MatchScore {
+    pub round: u32,
+    /// `None` when the last round ended with nobody left
+    pub round_winner: Option<String>,
+}
+
+/// The player who has won enough rounds to take the match, if any.
+fn match_winner<'a>(scoreboard: &'a Scoreboard, rules: &MatchRules) -> Option<&'a String> {
+    scoreboard
+        .leader()
+        .filter(|(_, score)| score.score >= rules.rounds_to_win)
+        .map(|(name, _)| name)
+}
+
+/// Where each round's maze seed comes from. Seeded from entropy, or from a
+/// fixed seed to replay the same mazes.
+#[derive(Resource, Debug)]
+pub struct MazeRng(pub StdRng);
+
+impl Default for MazeRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+/// The server's lobby as lines of text, filled in when playing online.
+/// Shown in place of the local players while it has anything in it.
+#[derive(Resource, Debug, Default)]
+pub struct LobbyRoster(pub Vec<String>);
+
+#[derive(Resource, Debug)]
+struct PhaseTimer(Timer);
+
+/// Centered text for whichever screen is showing.
+#[derive(Component)]
+struct Banner;
+
+/// Everything that belongs to one round and goes away with it.
+type RoundEntity = Or<(
+    With<Tank>,
+    With<Bullet>,
+    With<Wall>,
+    With<Pickup>,
+    With<Mine>,
+    With<Flag>,
+)>;
+
+pub struct GameFlowPlugin;
+impl Plugin for GameFlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .enable_state_scoped_entities::<AppState>()
+            .init_resource::<MatchRules>()
+            .init_resource::<MatchScore>()
+            .init_resource::<MazeSeed>()
+            .init_resource::<Maze>()
+            .init_resource::<MazeRng>()
+            .init_resource::<LobbyRoster>()
+            .insert_resource(PhaseTimer(Timer::from_seconds(0., TimerMode::Once)))
+            .add_systems(OnEnter(AppState::MainMenu), main_menu_setup)
+            .add_systems(
+                OnEnter(AppState::Lobby),
+                (despawn_round_entities, lobby_setup),
+            )
+            .add_systems(
+                OnEnter(AppState::Countdown),
+                (
+                    new_maze_seed.run_if(driven_locally),
+                    setup_walls,
+                    countdown_setup,
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(AppState::RoundOver), round_over_setup)
+            .add_systems(OnExit(AppState::RoundOver), despawn_round_entities)
+            .add_systems(OnEnter(AppState::MatchOver), match_over_setup)
+            .add_systems(
+                Update,
+                (
+                    main_menu_system.run_if(in_state(AppState::MainMenu)),
+                    lobby_system.run_if(in_state(AppState::Lobby)),
+                    countdown_system.run_if(in_state(AppState::Countdown)),
+                    round_end_system.run_if(in_state(AppState::InRound).and(driven_locally)),
+                    round_over_system.run_if(in_state(AppState::RoundOver)),
+                    match_over_system.run_if(in_state(AppState::MatchOver)),
+                ),
+            );
+    }
+}
+
+pub fn driven_locally(rules: Res<MatchRules>) -> bool {
+    rules.driven_locally
+}
+
+fn spawn_banner(commands: &mut Commands, state: AppState, text: impl Into<String>) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            StateScoped(state),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(text),
+                TextFont::from_font_size(32.),
+                TextLayout::new_with_justify(JustifyText::Center),
+                Banner,
+            ));
+        });
+}
+
+fn main_menu_setup(mut commands: Commands) {
+    spawn_banner(&mut commands, AppState::MainMenu, "TANKS\n\nEnter to play");
+}
+
+fn main_menu_system(keys: Res<ButtonInput<KeyCode>>, mut next_state: ResMut<NextState<AppState>>) {
+    if keys.just_pressed(KeyCode::Enter) {
+        next_state.set(AppState::Lobby);
+    }
+}
+
+fn lobby_setup(
+    mut commands: Commands,
+    mut score: ResMut<MatchScore>,
+    mut scoreboard: ResMut<Scoreboard>,
+) {
+    *score = MatchScore::default();
+    scoreboard.0.clear();
+    spawn_banner(&mut commands, AppState::Lobby, "");
+}
+
+fn lobby_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    (rules, mode_rules): (Res<MatchRules>, Res<ModeRules>),
+    (local_players, roster, palette): (Res<LocalPlayers>, Res<LobbyRoster>, Res<Palette>),
+    bindings: Res<Bindings>,
+    mut banner_query: Query<&mut Text, With<Banner>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if rules.driven_locally && keys.just_pressed(bindings.menu.ready) {
+        next_state.set(AppState::Countdown);
+        return;
+    }
+
+    let Ok(mut text) = banner_query.get_single_mut() else {
+        return;
+    };
+    let changed = local_players.is_changed()
+        || mode_rules.is_changed()
+        || roster.is_changed()
+        || palette.is_changed()
+        || bindings.is_changed();
+    if !changed && !text.0.is_empty() {
+        return;
+    }
+    let mode = mode_rules.mode;
+    text.0 = format!("LOBBY\n\n< {} >", mode.name());
+    if mode.team(0).is_some() {
+        let friendly_fire = if mode_rules.friendly_fire {
+            "on"
+        } else {
+            "off"
+        };
+        text.0 += &format!("  friendly fire {friendly_fire}");
+    }
+    text.0 += "\n\n";
+    if !roster.0.is_empty() {
+        text.0 += &roster.0.join("\n");
+        return;
+    }
+    for (index, slot) in local_players.0.iter().enumerate() {
+        text.0 += &format!(
+            "{}  < {:?} >  {}",
+            Player(index).name(),
+            slot.class,
+            palette.color_name(PlayerColor(index as u8))
+        );
+        if let Controls::Bot(difficulty) = slot.controls {
+            text.0 += &format!("  bot, {}", difficulty.name());
+        }
+        if let Some(team) = mode.team(index) {
+            text.0 += &format!("  {}", team.name());
+        }
+        text.0.push('\n');
+    }
+    text.0 += "\nTurn left / right to pick a class\nStart on a gamepad to join";
+    text.0 += &format!(
+        "\n{:?} for the game mode, {:?} for friendly fire",
+        bindings.menu.mode, bindings.menu.friendly_fire
+    );
+    text.0 += &format!(
+        "\nB adds a bot, N removes one, {:?} for bot difficulty",
+        bindings.menu.bot_difficulty
+    );
+    text.0 += &format!("\nF5 to change colours, {} now", palette.name());
+    if rules.driven_locally {
+        text.0 += &format!("\n{:?} to start", bindings.menu.ready);
+    }
+}
+
+fn new_maze_seed(mut seed: ResMut<MazeSeed>, mut rng: ResMut<MazeRng>) {
+    seed.0 = rng.0.gen();
+}
+
+fn countdown_setup(
+    mut commands: Commands,
+    (rules, local_players): (Res<MatchRules>, Res<LocalPlayers>),
+    mut score: ResMut<MatchScore>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut timer: ResMut<PhaseTimer>,
+) {
+    score.round += 1;
+    if rules.driven_locally {
+        for index in 0..local_players.0.len() {
+            scoreboard.entry(Player(index).name());
+        }
+    }
+    timer.0 = Timer::from_seconds(rules.countdown, TimerMode::Once);
+    spawn_banner(&mut commands, AppState::Countdown, "");
+}
+
+fn countdown_system(
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    score: Res<MatchScore>,
+    mut timer: ResMut<PhaseTimer>,
+    mut banner_query: Query<&mut Text, With<Banner>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    timer.0.tick(time.delta());
+    if let Ok(mut text) = banner_query.get_single_mut() {
+        text.0 = format!(
+            "Round {}\n\n{:.0}",
+            score.round,
+            timer.0.remaining_secs().ceil()
+        );
+    }
+    if rules.driven_locally && timer.0.finished() {
+        next_state.set(AppState::InRound);
+    }
+}
+
+/// Ends the round when the game mode says so and scores it.
+fn round_end_system(
+    (local_players, mode_rules): (Res<LocalPlayers>, Res<ModeRules>),
+    tank_query: Query<(&Player, Option<&Team>), With<Tank>>,
+    mut captured_events: EventReader<FlagCaptured>,
+    mut score: ResMut<MatchScore>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let alive: Vec<_> = tank_query
+        .iter()
+        .map(|(player, team)| (*player, team.copied()))
+        .collect();
+    let captured = captured_events.read().last().map(|event| event.team);
+    let winner = match mode_rules
+        .mode
+        .round_end(&alive, local_players.0.len(), captured)
+    {
+        RoundEnd::Continue => return,
+        RoundEnd::Won(winner) => Some(winner),
+        RoundEnd::Draw => None,
+    };
+
+    if let Some(winner) = &winner {
+        scoreboard.entry(winner.clone()).score += 1;
+    }
+    score.round_winner = winner;
+    next_state.set(AppState::RoundOver);
+}
+
+fn round_over_setup(
+    mut commands: Commands,
+    rules: Res<MatchRules>,
+    score: Res<MatchScore>,
+    mut timer: ResMut<PhaseTimer>,
+) {
+    timer.0 = Timer::from_seconds(rules.round_over, TimerMode::Once);
+    let text = match &score.round_winner {
+        Some(winner) if rules.driven_locally => {
+            format!("{} takes round {}", winner, score.round)
+        }
+        _ if rules.driven_locally => format!("Nobody takes round {}", score.round),
+        _ => String::from("Round over"),
+    };
+    spawn_banner(&mut commands, AppState::RoundOver, text);
+}
+
+fn round_over_system(
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    scoreboard: Res<Scoreboard>,
+    mut timer: ResMut<PhaseTimer>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !rules.driven_locally || !timer.0.tick(time.delta()).finished() {
+        return;
+    }
+    if match_winner(&scoreboard, &rules).is_some() {
+        next_state.set(AppState::MatchOver);
+    } else {
+        next_state.set(AppState::Countdown);
+    }
+}
+
+fn match_over_setup(mut commands: Commands, rules: Res<MatchRules>, scoreboard: Res<Scoreboard>) {
+    let mut text = match match_winner(&scoreboard, &rules) {
+        Some(winner) => format!("{winner} wins the match\n\n"),
+        None => String::from("Match over\n\n"),
+    };
+    for (name, score) in scoreboard.0.iter() {
+        text += &format!("{name}  {}\n", score.score);
+    }
+    if rules.driven_locally {
+        text += "\nEnter for the lobby";
+    }
+    spawn_banner(&mut commands, AppState::MatchOver, text);
+}
+
+fn match_over_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    rules: Res<MatchRules>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if rules.driven_locally && keys.just_pressed(KeyCode::Enter) {
+        next_state.set(AppState::Lobby);
+    }
+}
+
+fn despawn_round_entities(mut commands: Commands, query: Query<Entity, RoundEntity>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
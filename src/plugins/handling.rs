@@ -0,0 +1,103 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::Deserialize;
+
+/// How a tank drives. Loaded from `*.tank.ron` files under `assets/tanks/` so
+/// handling can be tuned without recompiling.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct TankStats {
+    /// Top forward speed in pixels per second
+    pub max_speed: f32,
+    /// Top reverse speed in pixels per second
+    pub reverse_speed: f32,
+    /// Pixels per second squared gained while the throttle is held
+    pub acceleration: f32,
+    /// Pixels per second squared lost while the throttle opposes the motion
+    pub braking: f32,
+    /// Pixels per second squared lost while coasting
+    pub friction: f32,
+    /// Degrees per second when standing still
+    pub rotation_speed: f32,
+    /// Share of `rotation_speed` left at top speed; turning gets harder the faster the tank goes.
+    pub turn_at_max_speed: f32,
+    /// Relative weight when two tanks push each other apart
+    pub mass: f32,
+}
+
+impl TankStats {
+    /// Used until a tank's stats file has loaded.
+    pub const DEFAULT: TankStats = TankStats {
+        max_speed: 150.,
+        reverse_speed: 100.,
+        acceleration: 400.,
+        braking: 600.,
+        friction: 300.,
+        rotation_speed: 90.,
+        turn_at_max_speed: 0.6,
+        mass: 1.,
+    };
+
+    /// Forward speed after `dt` seconds with `throttle` in [-1, 1], starting
+    /// from `speed`. `speed_multiplier` scales the top speeds.
+    pub fn accelerate(&self, speed: f32, throttle: f32, speed_multiplier: f32, dt: f32) -> f32 {
+        if throttle == 0.0 {
+            return move_towards(speed, 0.0, self.friction * dt);
+        }
+        if speed * throttle < 0.0 {
+            return move_towards(speed, 0.0, self.braking * dt);
+        }
+        let top_speed = if throttle > 0.0 {
+            self.max_speed
+        } else {
+            self.reverse_speed
+        };
+        let target = throttle * top_speed * speed_multiplier;
+        move_towards(speed, target, self.acceleration * dt)
+    }
+
+    /// Degrees per second the hull can turn while moving at `speed`.
+    pub fn turn_rate(&self, speed: f32) -> f32 {
+        let share = (speed.abs() / self.max_speed).min(1.0);
+        self.rotation_speed * (1.0 + (self.turn_at_max_speed - 1.0) * share)
+    }
+}
+
+impl Default for TankStats {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+fn move_towards(current: f32, target: f32, max_delta: f32) -> f32 {
+    if (target - current).abs() <= max_delta {
+        target
+    } else {
+        current + (target - current).signum() * max_delta
+    }
+}
+
+#[derive(Default)]
+pub struct TankStatsLoader;
+
+impl AssetLoader for TankStatsLoader {
+    type Asset = TankStats;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tank.ron"]
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use super::{
+    classes::{TankClassDef, TankClasses},
+    colors::{Palette, PlayerColor},
+    pickups::PowerUps,
+    players::{Player, MAX_LOCAL_PLAYERS},
+    shooting::{live_bullets, Bullet, Gun},
+    tank::Tank,
+    weapons::{Armory, EquippedWeapon, WeaponDef},
+};
+
+/// The HUD line for the local player with this index, in their colour.
+#[derive(Component)]
+struct AmmoText(usize);
+
+pub struct HudPlugin;
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup)
+            .add_systems(Update, ammo_text_system);
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            left: Val::Px(10.),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .with_children(|parent| {
+            for index in 0..MAX_LOCAL_PLAYERS {
+                parent.spawn((Text::new(""), AmmoText(index)));
+            }
+        });
+}
+
+/// What the HUD shows of a tank.
+type HudTank<'a> = (
+    Entity,
+    &'a Player,
+    &'a Tank,
+    &'a Gun,
+    &'a EquippedWeapon,
+    &'a PowerUps,
+    &'a PlayerColor,
+);
+
+fn ammo_text_system(
+    tank_query: Query<HudTank>,
+    bullet_query: Query<&Bullet>,
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    (classes, class_assets): (Res<TankClasses>, Res<Assets<TankClassDef>>),
+    palette: Res<Palette>,
+    mut text_query: Query<(&mut Text, &mut TextColor, &AmmoText)>,
+) {
+    for (mut text, mut text_color, line) in text_query.iter_mut() {
+        text.0.clear();
+        let Some((tank, player, hull, gun, weapon, power_ups, color)) =
+            tank_query.iter().find(|(_, player, ..)| player.0 == line.0)
+        else {
+            continue;
+        };
+        text_color.0 = palette.color(*color);
+
+        text.0 += &format!("{}  ", player.name());
+        if let Some(class) = classes.get(hull.class, &class_assets) {
+            text.0 += &format!("{}  ", class.name);
+        }
+        let def = armory.get(weapon.0, &weapon_assets);
+        let name = def.map_or("", |def| def.name.as_str());
+        let projectiles = def.map_or(1, WeaponDef::projectiles);
+        let shots = gun.shots_left(live_bullets(tank, &bullet_query), projectiles);
+        text.0 += &if gun.is_reloading() {
+            format!("{name}  Reloading...")
+        } else {
+            format!(
+                "{name}  Shots: {} / {}",
+                shots,
+                gun.max_live_bullets / projectiles
+            )
+        };
+        for (power_up, timer) in power_ups.0.iter() {
+            text.0 += &format!(
+                "  {} {:.0}s",
+                power_up.name(),
+                timer.remaining_secs().ceil()
+            );
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use super::{
+    bindings::{not_rebinding, Action, Bindings},
+    bots::Difficulty,
+    game_flow::AppState,
+    tank::Tank,
+};
+
+/// Stick deflection below which a stick counts as centred.
+const STICK_DEADZONE: f32 = 0.3;
+
+/// Where a player's input comes from.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub enum Controls {
+    /// `scheme` indexes `Bindings::keyboard`, `mouse_aim` gives this player
+    /// the mouse for aiming the turret.
+    Keyboard {
+        scheme: usize,
+        mouse_aim: bool,
+    },
+    Gamepad(Entity),
+    /// Driven by the computer, see `bots`
+    Bot(Difficulty),
+    /// Driven from outside the game through the training environment, see `gym`
+    Agent,
+}
+
+/// What a tank's driver asked for this frame. Filled in from the tank's
+/// `Controls` and read by every gameplay system instead of raw input.
+#[derive(Component, Debug, Clone, Default)]
+pub struct TankInput {
+    /// -1 full reverse to 1 full ahead
+    pub throttle: f32,
+    /// -1 hard right to 1 hard left
+    pub steer: f32,
+    pub fire: bool,
+    pub switch_weapon: bool,
+    pub lay_mine: bool,
+    pub place_barrier: bool,
+    pub toggle_aim: bool,
+    /// World point the turret should face, if the player is aiming
+    pub aim: Option<Vec2>,
+}
+
+pub struct InputPlugin;
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            (
+                read_input_system.run_if(in_state(AppState::InRound).and(not_rebinding)),
+                clear_input_system.run_if(not(in_state(AppState::InRound).and(not_rebinding))),
+            )
+                .after(InputSystem),
+        );
+    }
+}
+
+/// Gamepad button standing in for `action`. In a round driving comes from
+/// the sticks, the d-pad buttons are for menus.
+fn gamepad_button(action: Action) -> GamepadButton {
+    match action {
+        Action::Forward => GamepadButton::DPadUp,
+        Action::Back => GamepadButton::DPadDown,
+        Action::TurnLeft => GamepadButton::DPadLeft,
+        Action::TurnRight => GamepadButton::DPadRight,
+        Action::Fire => GamepadButton::South,
+        Action::SwitchWeapon => GamepadButton::North,
+        Action::LayMine => GamepadButton::West,
+        Action::PlaceBarrier => GamepadButton::East,
+        Action::ToggleAim => GamepadButton::RightThumb,
+    }
+}
+
+/// Whether a player just asked for `action`, for screens where they have no
+/// tank to read `TankInput` from.
+pub fn action_just_pressed(
+    controls: &Controls,
+    action: Action,
+    keys: &ButtonInput<KeyCode>,
+    bindings: &Bindings,
+    gamepads: &Query<&Gamepad>,
+) -> bool {
+    match controls {
+        Controls::Keyboard { scheme, .. } => bindings.scheme(*scheme).just_pressed(keys, action),
+        Controls::Gamepad(entity) => gamepads
+            .get(*entity)
+            .is_ok_and(|gamepad| gamepad.just_pressed(gamepad_button(action))),
+        Controls::Bot(_) | Controls::Agent => false,
+    }
+}
+
+fn axis(positive: bool, negative: bool) -> f32 {
+    match (positive, negative) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => 0.0,
+    }
+}
+
+/// World position of the mouse cursor, if it is over the window.
+fn cursor_position(
+    window_query: &Query<&Window>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = window_query.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor).ok()
+}
+
+fn read_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<Bindings>,
+    gamepads: Query<&Gamepad>,
+    window_query: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut tank_query: Query<(&Controls, &Transform, &mut TankInput), With<Tank>>,
+) {
+    let cursor = cursor_position(&window_query, &camera_query);
+
+    for (controls, transform, mut input) in tank_query.iter_mut() {
+        *input = match controls {
+            Controls::Keyboard { scheme, mouse_aim } => {
+                let scheme = bindings.scheme(*scheme);
+                TankInput {
+                    throttle: axis(
+                        scheme.pressed(&keys, Action::Forward),
+                        scheme.pressed(&keys, Action::Back),
+                    ),
+                    steer: axis(
+                        scheme.pressed(&keys, Action::TurnLeft),
+                        scheme.pressed(&keys, Action::TurnRight),
+                    ),
+                    fire: scheme.just_pressed(&keys, Action::Fire),
+                    switch_weapon: scheme.just_pressed(&keys, Action::SwitchWeapon),
+                    lay_mine: scheme.just_pressed(&keys, Action::LayMine),
+                    place_barrier: scheme.just_pressed(&keys, Action::PlaceBarrier),
+                    toggle_aim: scheme.just_pressed(&keys, Action::ToggleAim),
+                    aim: cursor.filter(|_| *mouse_aim),
+                }
+            }
+            Controls::Gamepad(entity) => {
+                let Ok(gamepad) = gamepads.get(*entity) else {
+                    *input = TankInput::default();
+                    continue;
+                };
+                let stick = gamepad.left_stick();
+                let dpad = gamepad.dpad();
+                let throttle = if stick.y.abs() > STICK_DEADZONE {
+                    stick.y
+                } else {
+                    dpad.y
+                };
+                let steer = if stick.x.abs() > STICK_DEADZONE {
+                    -stick.x
+                } else {
+                    -dpad.x
+                };
+                let aim = gamepad.right_stick();
+                TankInput {
+                    throttle,
+                    steer,
+                    fire: gamepad.just_pressed(gamepad_button(Action::Fire))
+                        || gamepad.just_pressed(GamepadButton::RightTrigger),
+                    switch_weapon: gamepad.just_pressed(gamepad_button(Action::SwitchWeapon)),
+                    lay_mine: gamepad.just_pressed(gamepad_button(Action::LayMine)),
+                    place_barrier: gamepad.just_pressed(gamepad_button(Action::PlaceBarrier)),
+                    toggle_aim: gamepad.just_pressed(gamepad_button(Action::ToggleAim)),
+                    aim: (aim.length() > STICK_DEADZONE).then(|| transform.translation.xy() + aim),
+                }
+            }
+            // Bots fill in their own input, agents get theirs from `gym`
+            Controls::Bot(_) | Controls::Agent => continue,
+        };
+    }
+}
+
+/// Tanks sit still outside a round, and while keys are being rebound so the
+/// keys pressed don't also drive them.
+fn clear_input_system(mut tank_query: Query<&mut TankInput>) {
+    for mut input in tank_query.iter_mut() {
+        *input = TankInput::default();
+    }
+}
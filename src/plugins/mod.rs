@@ -1,3 +1,19 @@
+pub mod bindings;
+pub mod bots;
+pub mod classes;
 pub mod collision;
+pub mod colors;
+pub mod combat;
+pub mod debug;
+pub mod deployables;
+pub mod game_flow;
+pub mod handling;
+pub mod hud;
+pub mod input;
+pub mod modes;
+pub mod pickups;
+pub mod players;
+pub mod scoring;
 pub mod shooting;
 pub mod tank;
+pub mod weapons;
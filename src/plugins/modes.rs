@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+pub use shared::modes::{GameMode, Team};
+
+use crate::{
+    constants::{GRID_CELL_SIZE, GRID_CELL_VERTICAL_AMOUNT},
+    grid::cell_center,
+};
+
+use super::{
+    bindings::Bindings,
+    colors::Palette,
+    game_flow::{AppState, MatchRules},
+    players::{spawn_point, Player},
+    tank::Tank,
+};
+
+const FLAG_SIZE: f32 = 16.;
+/// How close a tank has to get to a flag to take it.
+const FLAG_PICKUP_RADIUS: f32 = 30.;
+const BASE_OUTLINE_ALPHA: f32 = 0.6;
+
+/// How a mode plays out here: who is on which team, where everyone starts
+/// and when a round is over. The modes themselves are shared with the server.
+pub trait ModeRounds {
+    /// The team player `index` plays for, `None` in free for all.
+    fn team(&self, index: usize) -> Option<Team>;
+    /// Where player `index` starts a round: a corner of their own in free for
+    /// all, next to their team's base otherwise.
+    fn spawn_point(&self, index: usize) -> Vec2;
+    /// Whether the round is over, given the tanks still standing, how many
+    /// players started it and the team that just captured a flag, if any.
+    fn round_end(
+        &self,
+        alive: &[(Player, Option<Team>)],
+        players: usize,
+        captured: Option<Team>,
+    ) -> RoundEnd;
+    /// Whether `killer` taking out `victim` counts as a kill.
+    fn is_kill(&self, killer: Player, victim: Player) -> bool;
+}
+
+impl ModeRounds for GameMode {
+    fn team(&self, index: usize) -> Option<Team> {
+        match self {
+            GameMode::FreeForAll => None,
+            GameMode::TeamDeathmatch | GameMode::CaptureTheFlag => Some(Team::ALL[index % 2]),
+        }
+    }
+
+    fn spawn_point(&self, index: usize) -> Vec2 {
+        let Some(team) = self.team(index) else {
+            return spawn_point(index);
+        };
+        let (x, y) = team.base_cell();
+        // Teammates line up along the base column, alternating above and below it
+        let nth = (index / 2) as i32;
+        let offset = if nth % 2 == 0 {
+            nth / 2
+        } else {
+            -(nth / 2 + 1)
+        };
+        let y = (y as i32 + offset).clamp(0, GRID_CELL_VERTICAL_AMOUNT as i32 - 1);
+        cell_center(x, y as usize)
+    }
+
+    fn round_end(
+        &self,
+        alive: &[(Player, Option<Team>)],
+        players: usize,
+        captured: Option<Team>,
+    ) -> RoundEnd {
+        if let Some(team) = captured {
+            return RoundEnd::Won(team.name().to_string());
+        }
+        let sides = if self.team(0).is_some() {
+            players.min(Team::ALL.len())
+        } else {
+            players
+        };
+        let last_standing = if sides > 1 { 1 } else { 0 };
+
+        match self {
+            GameMode::FreeForAll => {
+                if alive.len() > last_standing {
+                    return RoundEnd::Continue;
+                }
+                alive
+                    .first()
+                    .map_or(RoundEnd::Draw, |(player, _)| RoundEnd::Won(player.name()))
+            }
+            GameMode::TeamDeathmatch | GameMode::CaptureTheFlag => {
+                let teams: Vec<Team> = Team::ALL
+                    .into_iter()
+                    .filter(|team| alive.iter().any(|(_, alive)| *alive == Some(*team)))
+                    .collect();
+                if teams.len() > last_standing {
+                    return RoundEnd::Continue;
+                }
+                teams.first().map_or(RoundEnd::Draw, |team| {
+                    RoundEnd::Won(team.name().to_string())
+                })
+            }
+        }
+    }
+
+    fn is_kill(&self, killer: Player, victim: Player) -> bool {
+        killer != victim
+            && (*self == GameMode::FreeForAll || self.team(killer.0) != self.team(victim.0))
+    }
+}
+
+/// What the mode makes of the round so far. Rounds are won by a player in
+/// free for all and by a team otherwise, named as on the scoreboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundEnd {
+    Continue,
+    Won(String),
+    /// Nobody left standing
+    Draw,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ModeRules {
+    pub mode: GameMode,
+    /// Whether shots hurt teammates in the team modes
+    pub friendly_fire: bool,
+}
+
+impl Default for ModeRules {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::FreeForAll,
+            friendly_fire: false,
+        }
+    }
+}
+
+impl ModeRules {
+    /// Whether a shot from `source` may hurt `target`.
+    pub fn can_damage(&self, source: Option<Team>, target: Option<Team>) -> bool {
+        self.friendly_fire || source.is_none() || source != target
+    }
+}
+
+/// A team's flag. It sits in the team's base until an enemy tank drives
+/// over it, then rides along with that tank until it is captured or the
+/// carrier is destroyed.
+#[derive(Component, Debug)]
+pub struct Flag {
+    pub team: Team,
+    pub carrier: Option<Entity>,
+}
+
+/// Sent when a tank brings the enemy flag into its own base while its own
+/// flag is at home.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FlagCaptured {
+    pub team: Team,
+}
+
+pub struct ModesPlugin;
+impl Plugin for ModesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ModeRules>()
+            .add_event::<FlagCaptured>()
+            .add_systems(
+                Update,
+                select_mode_system
+                    .run_if(in_state(AppState::Lobby))
+                    .run_if(|rules: Res<MatchRules>| rules.driven_locally),
+            )
+            .add_systems(
+                OnEnter(AppState::Countdown),
+                spawn_flags.run_if(|rules: Res<ModeRules>| rules.mode == GameMode::CaptureTheFlag),
+            )
+            .add_systems(
+                Update,
+                (flag_pickup_system, flag_carry_system, flag_capture_system)
+                    .chain()
+                    .run_if(in_state(AppState::InRound))
+                    .run_if(|rules: Res<MatchRules>| rules.driven_locally),
+            )
+            .add_systems(
+                Update,
+                draw_bases_system
+                    .run_if(|rules: Res<ModeRules>| rules.mode == GameMode::CaptureTheFlag),
+            );
+    }
+}
+
+/// The menu keys step through the modes and toggle friendly fire, in the lobby.
+fn select_mode_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut rules: ResMut<ModeRules>,
+) {
+    if keys.just_pressed(bindings.menu.mode) {
+        let current = GameMode::ALL
+            .iter()
+            .position(|mode| *mode == rules.mode)
+            .unwrap_or(0);
+        rules.mode = GameMode::ALL[(current + 1) % GameMode::ALL.len()];
+    }
+    if keys.just_pressed(bindings.menu.friendly_fire) {
+        rules.friendly_fire = !rules.friendly_fire;
+    }
+}
+
+fn spawn_flags(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    palette: Res<Palette>,
+) {
+    for team in Team::ALL {
+        let (x, y) = team.base_cell();
+        let position = cell_center(x, y);
+        commands.spawn((
+            Mesh2d(meshes.add(Rectangle::new(FLAG_SIZE, FLAG_SIZE))),
+            MeshMaterial2d(materials.add(palette.team_color(team))),
+            Transform::from_xyz(position.x, position.y, 0.5),
+            Flag {
+                team,
+                carrier: None,
+            },
+        ));
+    }
+}
+
+fn flag_pickup_system(
+    mut flag_query: Query<(&mut Flag, &Transform)>,
+    tank_query: Query<(Entity, &Team, &Transform), With<Tank>>,
+) {
+    for (mut flag, flag_transform) in flag_query.iter_mut() {
+        if flag.carrier.is_some() {
+            continue;
+        }
+        let position = flag_transform.translation.xy();
+        flag.carrier = tank_query
+            .iter()
+            .find(|(_, team, transform)| {
+                **team != flag.team
+                    && transform.translation.xy().distance(position) <= FLAG_PICKUP_RADIUS
+            })
+            .map(|(tank, ..)| tank);
+    }
+}
+
+/// Keeps carried flags on their carrier, and sends a flag home when its
+/// carrier is gone.
+fn flag_carry_system(
+    mut flag_query: Query<(&mut Flag, &mut Transform), Without<Tank>>,
+    tank_query: Query<&Transform, With<Tank>>,
+) {
+    for (mut flag, mut transform) in flag_query.iter_mut() {
+        let Some(carrier) = flag.carrier else {
+            continue;
+        };
+        let position = match tank_query.get(carrier) {
+            Ok(carrier_transform) => carrier_transform.translation.xy(),
+            Err(_) => {
+                flag.carrier = None;
+                let (x, y) = flag.team.base_cell();
+                cell_center(x, y)
+            }
+        };
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+fn flag_capture_system(
+    flag_query: Query<&Flag>,
+    tank_query: Query<(&Team, &Transform), With<Tank>>,
+    mut captured_events: EventWriter<FlagCaptured>,
+) {
+    for flag in flag_query.iter() {
+        let Some((team, transform)) = flag
+            .carrier
+            .and_then(|carrier| tank_query.get(carrier).ok())
+        else {
+            continue;
+        };
+        let own_flag_home = flag_query
+            .iter()
+            .any(|own| own.team == *team && own.carrier.is_none());
+        if own_flag_home && team.in_base(transform.translation.xy()) {
+            captured_events.send(FlagCaptured { team: *team });
+        }
+    }
+}
+
+fn draw_bases_system(mut gizmos: Gizmos, palette: Res<Palette>) {
+    for team in Team::ALL {
+        let (x, y) = team.base_cell();
+        gizmos.rect_2d(
+            Isometry2d::from_translation(cell_center(x, y)),
+            Vec2::splat(GRID_CELL_SIZE - 8.),
+            palette.team_color(team).with_alpha(BASE_OUTLINE_ALPHA),
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use bevy::{color::palettes::css, prelude::*, render::primitives::Aabb};
+use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+pub use shared::pickups::PickupKind;
+
+use crate::{
+    collider::Collider,
+    constants::{GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_VERTICAL_AMOUNT},
+    grid::{cell_at, cell_center},
+    walls::MazeSeed,
+};
+
+use super::{
+    game_flow::AppState,
+    tank::Tank,
+    weapons::{EquippedWeapon, WeaponKind},
+};
+
+const PICKUP_SIZE: f32 = 24.;
+/// Speed multiplier while `PowerUp::SpeedBoost` is active.
+pub const SPEED_BOOST_MULTIPLIER: f32 = 1.5;
+/// Seconds a ghost bullet lives, they would otherwise drift through the maze forever.
+pub const GHOST_BULLET_LIFETIME: f32 = 3.;
+
+/// What a pickup does to a tank and how it is drawn. The kinds themselves
+/// are shared with the server.
+trait PickupEffects {
+    /// The weapon this pickup lends, if it is a weapon pickup.
+    fn weapon(&self) -> Option<WeaponKind>;
+    fn power_up(&self) -> Option<PowerUp>;
+    fn label(&self) -> &'static str;
+    fn color(&self) -> Color;
+}
+
+impl PickupEffects for PickupKind {
+    fn weapon(&self) -> Option<WeaponKind> {
+        match self {
+            PickupKind::Shotgun => Some(WeaponKind::Shotgun),
+            PickupKind::Laser => Some(WeaponKind::Laser),
+            PickupKind::Frag => Some(WeaponKind::Frag),
+            PickupKind::Missile => Some(WeaponKind::Missile),
+            _ => None,
+        }
+    }
+
+    fn power_up(&self) -> Option<PowerUp> {
+        match self {
+            PickupKind::Shield => Some(PowerUp::Shield),
+            PickupKind::SpeedBoost => Some(PowerUp::SpeedBoost),
+            PickupKind::GhostBullets => Some(PowerUp::GhostBullets),
+            PickupKind::MineLayer => Some(PowerUp::MineLayer),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            PickupKind::Shotgun => "SG",
+            PickupKind::Laser => "L",
+            PickupKind::Frag => "F",
+            PickupKind::Missile => "M",
+            PickupKind::Shield => "Sh",
+            PickupKind::SpeedBoost => "Sp",
+            PickupKind::GhostBullets => "G",
+            PickupKind::MineLayer => "Mn",
+        }
+    }
+
+    fn color(&self) -> Color {
+        if self.weapon().is_some() {
+            css::ORANGE.into()
+        } else {
+            css::DODGER_BLUE.into()
+        }
+    }
+}
+
+/// Temporary abilities a tank can pick up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUp {
+    /// Ignores all damage
+    Shield,
+    /// Drives `SPEED_BOOST_MULTIPLIER` times faster
+    SpeedBoost,
+    /// Bullets fly through walls
+    GhostBullets,
+    /// Can drop mines
+    MineLayer,
+}
+
+impl PowerUp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUp::Shield => "Shield",
+            PowerUp::SpeedBoost => "Speed",
+            PowerUp::GhostBullets => "Ghost",
+            PowerUp::MineLayer => "Mines",
+        }
+    }
+}
+
+/// Power-ups a tank holds, each with the time it has left.
+#[derive(Component, Debug, Default)]
+pub struct PowerUps(pub Vec<(PowerUp, Timer)>);
+
+impl PowerUps {
+    pub fn has(&self, power_up: PowerUp) -> bool {
+        self.0.iter().any(|(held, _)| *held == power_up)
+    }
+
+    /// Gives `power_up` for `duration` seconds, restarting it if already held.
+    pub fn grant(&mut self, power_up: PowerUp, duration: f32) {
+        self.0.retain(|(held, _)| *held != power_up);
+        self.0
+            .push((power_up, Timer::from_seconds(duration, TimerMode::Once)));
+    }
+}
+
+/// A weapon lent by a pickup; `previous` is given back once `timer` finishes.
+#[derive(Component, Debug)]
+pub struct TemporaryWeapon {
+    pub previous: WeaponKind,
+    pub timer: Timer,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Pickup {
+    /// Shared with the server so both sides agree on which pickup was taken.
+    pub id: u32,
+    pub kind: PickupKind,
+}
+
+/// Sent when a tank drives over a pickup. Online this only asks the server
+/// for it, see `PickupRules::spawn_locally`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PickupCollected {
+    pub tank: Entity,
+    pub id: u32,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct PickupRules {
+    /// Seconds between two spawns
+    pub spawn_interval: f32,
+    /// No more pickups are spawned while this many lie in the maze.
+    pub max_pickups: usize,
+    /// Seconds a power-up or lent weapon lasts
+    pub duration: f32,
+    /// Off when the server decides where pickups appear and who takes them.
+    pub spawn_locally: bool,
+}
+
+impl Default for PickupRules {
+    fn default() -> Self {
+        Self {
+            spawn_interval: 8.,
+            max_pickups: 3,
+            duration: 10.,
+            spawn_locally: true,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct PickupSpawner {
+    timer: Timer,
+    next_id: u32,
+    /// Picks where pickups go and what they are. Reseeded from the maze seed
+    /// every round, so the same seed brings the same pickups.
+    rng: StdRng,
+}
+
+pub struct PickupsPlugin;
+impl Plugin for PickupsPlugin {
+    fn build(&self, app: &mut App) {
+        let rules = PickupRules::default();
+        app.insert_resource(PickupSpawner {
+            timer: Timer::from_seconds(rules.spawn_interval, TimerMode::Repeating),
+            next_id: 0,
+            rng: StdRng::from_entropy(),
+        })
+        .init_resource::<PickupRules>()
+        .add_event::<PickupCollected>()
+        .add_systems(OnEnter(AppState::InRound), reseed_pickups_system)
+        .add_systems(
+            Update,
+            (
+                spawn_pickup_system
+                    .run_if(in_state(AppState::InRound))
+                    .run_if(|rules: Res<PickupRules>| rules.spawn_locally),
+                collect_pickup_system,
+                power_up_system,
+                temporary_weapon_system,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn reseed_pickups_system(seed: Res<MazeSeed>, mut spawner: ResMut<PickupSpawner>) {
+    spawner.rng = StdRng::seed_from_u64(seed.0);
+}
+
+fn spawn_pickup_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+    (rules, mut spawner): (Res<PickupRules>, ResMut<PickupSpawner>),
+    pickup_query: Query<&Transform, With<Pickup>>,
+    tank_query: Query<&Transform, With<Tank>>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    if pickup_query.iter().count() >= rules.max_pickups {
+        return;
+    }
+
+    let occupied: Vec<(i32, i32)> = pickup_query
+        .iter()
+        .chain(tank_query.iter())
+        .map(|transform| cell_at(transform.translation.xy()))
+        .collect();
+    let rng = &mut spawner.rng;
+    let free_cell = (0..GRID_CELL_HORIZONTAL_AMOUNT as usize)
+        .flat_map(|x| (0..GRID_CELL_VERTICAL_AMOUNT as usize).map(move |y| (x, y)))
+        .filter(|&(x, y)| !occupied.contains(&(x as i32, y as i32)))
+        .choose(rng);
+    let Some(cell) = free_cell else {
+        return;
+    };
+    let Some(&kind) = PickupKind::ALL.iter().choose(rng) else {
+        return;
+    };
+
+    let pickup = Pickup {
+        id: spawner.next_id,
+        kind,
+    };
+    spawner.next_id += 1;
+    spawn_pickup(&mut commands, &mut meshes, &mut materials, pickup, cell);
+}
+
+/// Spawns `pickup` in the middle of maze cell `cell`.
+pub fn spawn_pickup(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    pickup: Pickup,
+    cell: (usize, usize),
+) -> Entity {
+    let position = cell_center(cell.0, cell.1);
+    let aabb = Aabb {
+        center: position.extend(0.).into(),
+        half_extents: Vec3::new(PICKUP_SIZE / 2., PICKUP_SIZE / 2., 0.).into(),
+    };
+    commands
+        .spawn((
+            Mesh2d(meshes.add(Rectangle::new(PICKUP_SIZE, PICKUP_SIZE))),
+            MeshMaterial2d(materials.add(pickup.kind.color())),
+        ))
+        .insert(Transform::from_xyz(position.x, position.y, -0.5))
+        .insert(pickup)
+        .insert(Collider::Aabb(aabb))
+        .with_children(|parent| {
+            parent.spawn((
+                Text2d::new(pickup.kind.label()),
+                TextFont {
+                    font_size: 12.,
+                    ..default()
+                },
+                Transform::from_xyz(0., 0., 0.1),
+            ));
+        })
+        .id()
+}
+
+/// What a tank holds that a pickup can change.
+pub type PickupTaker<'a> = (
+    &'a mut PowerUps,
+    &'a mut EquippedWeapon,
+    Option<Mut<'a, TemporaryWeapon>>,
+);
+
+/// Gives `tank` what a `kind` pickup holds for `duration` seconds.
+pub fn apply_pickup(
+    commands: &mut Commands,
+    tank: Entity,
+    kind: PickupKind,
+    duration: f32,
+    (power_ups, weapon, temporary): PickupTaker,
+) {
+    if let Some(power_up) = kind.power_up() {
+        power_ups.grant(power_up, duration);
+    }
+    if let Some(lent) = kind.weapon() {
+        let timer = Timer::from_seconds(duration, TimerMode::Once);
+        // Keep the weapon from before the first pickup, not the one lent last
+        match temporary {
+            Some(mut temporary) => temporary.timer = timer,
+            None => {
+                commands.entity(tank).insert(TemporaryWeapon {
+                    previous: weapon.0,
+                    timer,
+                });
+            }
+        }
+        weapon.0 = lent;
+    }
+}
+
+fn collect_pickup_system(
+    mut commands: Commands,
+    rules: Res<PickupRules>,
+    pickup_query: Query<(Entity, &Pickup, &Collider)>,
+    // Every tank carries `PowerUps`, so no `With<Tank>` filter is needed
+    mut tank_query: Query<(
+        Entity,
+        &Collider,
+        &mut PowerUps,
+        &mut EquippedWeapon,
+        Option<&mut TemporaryWeapon>,
+    )>,
+    mut collected_events: EventWriter<PickupCollected>,
+) {
+    for (pickup_entity, pickup, pickup_collider) in pickup_query.iter() {
+        let tank = tank_query
+            .iter_mut()
+            .find(|(_, tank_collider, ..)| tank_collider.collides_with(pickup_collider));
+        let Some((tank, _, mut power_ups, mut weapon, temporary)) = tank else {
+            continue;
+        };
+
+        collected_events.send(PickupCollected {
+            tank,
+            id: pickup.id,
+        });
+        // Online the pickup stays until the server hands it to someone
+        if !rules.spawn_locally {
+            continue;
+        }
+        let taker = (power_ups.as_mut(), weapon.as_mut(), temporary);
+        apply_pickup(&mut commands, tank, pickup.kind, rules.duration, taker);
+        commands.entity(pickup_entity).despawn_recursive();
+    }
+}
+
+fn power_up_system(time: Res<Time>, mut query: Query<&mut PowerUps>) {
+    for mut power_ups in query.iter_mut() {
+        power_ups
+            .0
+            .retain_mut(|(_, timer)| !timer.tick(time.delta()).finished());
+    }
+}
+
+fn temporary_weapon_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut TemporaryWeapon, &mut EquippedWeapon)>,
+) {
+    for (entity, mut temporary, mut weapon) in query.iter_mut() {
+        if temporary.timer.tick(time.delta()).finished() {
+            weapon.0 = temporary.previous;
+            commands.entity(entity).remove::<TemporaryWeapon>();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use crate::{
+    constants::{GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_VERTICAL_AMOUNT},
+    grid::cell_center,
+};
+
+use super::{
+    classes::TankClass,
+    game_flow::{driven_locally, AppState},
+    input::Controls,
+};
+
+pub const MAX_LOCAL_PLAYERS: usize = 4;
+
+/// Index of the local player driving a tank, into `LocalPlayers`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Player(pub usize);
+
+impl Player {
+    pub fn name(&self) -> String {
+        format!("P{}", self.0 + 1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerSlot {
+    pub controls: Controls,
+    pub class: TankClass,
+}
+
+/// Everyone playing on this machine. Two keyboard players to start with,
+/// gamepads join by pressing Start in the lobby. Online only the first plays.
+#[derive(Resource, Debug, Clone)]
+pub struct LocalPlayers(pub Vec<PlayerSlot>);
+
+impl Default for LocalPlayers {
+    fn default() -> Self {
+        Self(vec![
+            PlayerSlot {
+                controls: Controls::Keyboard {
+                    scheme: 0,
+                    mouse_aim: true,
+                },
+                class: TankClass::default(),
+            },
+            PlayerSlot {
+                controls: Controls::Keyboard {
+                    scheme: 1,
+                    mouse_aim: false,
+                },
+                class: TankClass::default(),
+            },
+        ])
+    }
+}
+
+/// Starting position of player `index`, one maze corner each.
+pub fn spawn_point(index: usize) -> Vec2 {
+    let max_x = GRID_CELL_HORIZONTAL_AMOUNT as usize - 1;
+    let max_y = GRID_CELL_VERTICAL_AMOUNT as usize - 1;
+    let (x, y) = [(0, max_y), (max_x, 0), (max_x, max_y), (0, 0)][index % 4];
+    cell_center(x, y)
+}
+
+pub struct PlayersPlugin;
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LocalPlayers>().add_systems(
+            Update,
+            join_gamepad_system.run_if(in_state(AppState::Lobby).and(driven_locally)),
+        );
+    }
+}
+
+fn join_gamepad_system(
+    gamepads: Query<(Entity, &Gamepad)>,
+    mut local_players: ResMut<LocalPlayers>,
+) {
+    for (entity, gamepad) in gamepads.iter() {
+        if !gamepad.just_pressed(GamepadButton::Start) {
+            continue;
+        }
+        let controls = Controls::Gamepad(entity);
+        let joined = local_players.0.iter().any(|slot| slot.controls == controls);
+        if joined || local_players.0.len() >= MAX_LOCAL_PLAYERS {
+            continue;
+        }
+        local_players.0.push(PlayerSlot {
+            controls,
+            class: TankClass::default(),
+        });
+    }
+}
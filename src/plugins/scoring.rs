@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+pub use shared::protocol::PlayerScore;
+
+use super::{
+    combat::{damage_system, TankDestroyed},
+    game_flow::MatchRules,
+    modes::{ModeRounds, ModeRules},
+};
+
+/// Seconds a kill stays in the feed.
+const KILL_FEED_LIFETIME: f32 = 5.;
+const KILL_FEED_LENGTH: usize = 5;
+const SCOREBOARD_KEY: KeyCode = KeyCode::Tab;
+
+/// Scores by player name. Kept here for local matches and copied from the
+/// server's game state in networked ones.
+#[derive(Resource, Debug, Default)]
+pub struct Scoreboard(pub BTreeMap<String, PlayerScore>);
+
+impl Scoreboard {
+    pub fn entry(&mut self, name: String) -> &mut PlayerScore {
+        self.0.entry(name).or_default()
+    }
+
+    /// The player with the best score, if any has scored.
+    pub fn leader(&self) -> Option<(&String, &PlayerScore)> {
+        self.0
+            .iter()
+            .filter(|(_, score)| score.score > 0)
+            .max_by_key(|(_, score)| score.score)
+    }
+}
+
+/// Recent kills, newest last.
+#[derive(Resource, Debug, Default)]
+pub struct KillFeed(Vec<(String, Timer)>);
+
+impl KillFeed {
+    pub fn push(&mut self, killer: Option<&str>, victim: &str) {
+        let line = match killer {
+            Some(killer) if killer != victim => format!("{killer} destroyed {victim}"),
+            Some(_) => format!("{victim} destroyed themselves"),
+            None => format!("{victim} was destroyed"),
+        };
+        self.0.push((
+            line,
+            Timer::from_seconds(KILL_FEED_LIFETIME, TimerMode::Once),
+        ));
+        if self.0.len() > KILL_FEED_LENGTH {
+            self.0.remove(0);
+        }
+    }
+}
+
+#[derive(Component)]
+struct KillFeedText;
+
+#[derive(Component)]
+struct ScoreboardText;
+
+pub struct ScoringPlugin;
+impl Plugin for ScoringPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Scoreboard>()
+            .init_resource::<KillFeed>()
+            .add_systems(Startup, setup)
+            .add_systems(
+                Update,
+                (
+                    tally_kills_system
+                        .after(damage_system)
+                        .run_if(|rules: Res<MatchRules>| rules.driven_locally),
+                    kill_feed_system,
+                    scoreboard_system,
+                ),
+            );
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextLayout::new_with_justify(JustifyText::Right),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            right: Val::Px(10.),
+            ..default()
+        },
+        KillFeedText,
+    ));
+    commands.spawn((
+        Text::new(""),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.),
+            left: Val::Percent(35.),
+            padding: UiRect::all(Val::Px(12.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.7)),
+        Visibility::Hidden,
+        ScoreboardText,
+    ));
+}
+
+fn tally_kills_system(
+    mut destroyed_events: EventReader<TankDestroyed>,
+    mode_rules: Res<ModeRules>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut kill_feed: ResMut<KillFeed>,
+) {
+    for event in destroyed_events.read() {
+        let Some(victim) = event.victim_player else {
+            continue;
+        };
+        scoreboard.entry(victim.name()).deaths += 1;
+        if let Some(killer) = event.killer_player {
+            if mode_rules.mode.is_kill(killer, victim) {
+                scoreboard.entry(killer.name()).kills += 1;
+            }
+        }
+        let killer = event.killer_player.map(|killer| killer.name());
+        kill_feed.push(killer.as_deref(), &victim.name());
+    }
+}
+
+fn kill_feed_system(
+    time: Res<Time>,
+    mut kill_feed: ResMut<KillFeed>,
+    mut text_query: Query<&mut Text, With<KillFeedText>>,
+) {
+    for (_, timer) in kill_feed.0.iter_mut() {
+        timer.tick(time.delta());
+    }
+    kill_feed.0.retain(|(_, timer)| !timer.finished());
+
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    text.0 = kill_feed
+        .0
+        .iter()
+        .map(|(line, _)| line.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+}
+
+/// Holding Tab shows everyone's score, kills and deaths.
+fn scoreboard_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    scoreboard: Res<Scoreboard>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<ScoreboardText>>,
+) {
+    let Ok((mut text, mut visibility)) = text_query.get_single_mut() else {
+        return;
+    };
+    if !keys.pressed(SCOREBOARD_KEY) {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    }
+    visibility.set_if_neq(Visibility::Inherited);
+
+    let mut rows: Vec<_> = scoreboard.0.iter().collect();
+    rows.sort_by(|a, b| b.1.score.cmp(&a.1.score).then(b.1.kills.cmp(&a.1.kills)));
+    text.0 = format!(
+        "{:<12}{:>7}{:>7}{:>7}\n",
+        "Player", "Score", "Kills", "Deaths"
+    );
+    for (name, score) in rows {
+        text.0 += &format!(
+            "{:<12}{:>7}{:>7}{:>7}\n",
+            name, score.score, score.kills, score.deaths
+        );
+    }
+}
@@ -1,43 +1,292 @@
+use std::time::Duration;
+
 use bevy::{prelude::*, render::primitives::Aabb};
 
 use crate::{
-    constants::{BULLET_HALF_EXTENTS, BULLET_OFFSET, BULLET_SIZE, BULLET_SPEED},
+    constants::{
+        BULLET_FADE_OUT, BULLET_LIFETIME, BULLET_MAX_BOUNCES, BULLET_OFFSET, BULLET_WALL_COOLDOWN,
+        FIRE_COOLDOWN, MAX_LIVE_BULLETS,
+    },
     Tank, Velocity,
 };
 
-use super::collision::Dynamic;
+use super::{
+    collision::Dynamic,
+    input::TankInput,
+    pickups::{PowerUp, PowerUps, GHOST_BULLET_LIFETIME},
+    tank::{turret_angle, Turret},
+    weapons::{Armory, EquippedWeapon, LaserFired, ProjectileBehavior, WeaponDef, WeaponKind},
+};
 use crate::collider::Collider;
 
+/// When a bullet is removed without hitting a tank. As a resource it is the
+/// match-wide default; weapons can give their bullets a different one.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct BulletLifetime {
+    /// Seconds, `None` to let bullets live until they run out of bounces
+    pub max_age: Option<f32>,
+    /// Bounces survived; the next wall hit removes the bullet. `None` for unlimited.
+    pub max_bounces: Option<u8>,
+    /// Seconds before `max_age` over which the bullet fades out
+    pub fade_out: f32,
+}
+
+impl Default for BulletLifetime {
+    fn default() -> Self {
+        Self {
+            max_age: Some(BULLET_LIFETIME),
+            max_bounces: Some(BULLET_MAX_BOUNCES),
+            fade_out: BULLET_FADE_OUT,
+        }
+    }
+}
+
+impl BulletLifetime {
+    /// Whether a bullet of `age` seconds with `bounce_count` bounces has expired.
+    pub fn expiry(&self, age: f32, bounce_count: u8) -> Option<ExpiryReason> {
+        if self.max_bounces.is_some_and(|max| bounce_count > max) {
+            Some(ExpiryReason::Bounces)
+        } else if self.max_age.is_some_and(|max| age >= max) {
+            Some(ExpiryReason::Age)
+        } else {
+            None
+        }
+    }
+
+    /// Opacity of a bullet of `age` seconds, dropping to zero as it nears `max_age`.
+    pub fn opacity(&self, age: f32) -> f32 {
+        match self.max_age {
+            Some(max_age) if self.fade_out > 0.0 => {
+                ((max_age - age) / self.fade_out).clamp(0.0, 1.0)
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryReason {
+    Age,
+    Bounces,
+}
+
+/// Sent when a bullet is removed because its lifetime ran out, as opposed to hitting a tank.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BulletExpired {
+    pub bullet: Entity,
+    pub owner: Entity,
+    pub weapon: WeaponKind,
+    pub position: Vec2,
+    pub reason: ExpiryReason,
+}
+
 #[derive(Component, Debug)]
 pub struct Bullet {
     /// Angle in degrees
     pub angle: f32,
     pub speed: f32,
+    /// Radius of the drawn shell, the collider is half as wide
+    pub size: f32,
+    pub damage: f32,
+    /// Weapon that fired the bullet, decides how it behaves in flight and on expiry.
+    pub weapon: WeaponKind,
     pub bounce_count: u8,
-    pub last_hit_wall: Option<Vec3>,
+    /// Seconds since the bullet was fired
+    pub age: f32,
+    pub lifetime: BulletLifetime,
+    /// Tank that fired the bullet, credited with any kill it makes.
+    pub owner: Entity,
+    /// Set once the bullet has cleared its owner's hull or bounced; until then
+    /// it can't hit the tank that fired it.
+    pub left_owner: bool,
+    /// Wall bounced off most recently, ignored until `wall_cooldown` finishes.
+    pub last_hit_wall: Option<Entity>,
+    pub wall_cooldown: Timer,
+    /// Flies through walls instead of bouncing off them.
+    pub ghost: bool,
 }
 
 impl Bullet {
-    fn new(angle: f32) -> Self {
+    pub fn new(
+        angle: f32,
+        owner: Entity,
+        weapon: WeaponKind,
+        def: &WeaponDef,
+        lifetime: BulletLifetime,
+    ) -> Self {
         Self {
             angle,
-            speed: BULLET_SPEED,
+            speed: def.speed,
+            size: def.size,
+            damage: def.damage,
+            weapon,
             bounce_count: 0,
+            age: 0.,
+            lifetime,
+            owner,
+            left_owner: false,
             last_hit_wall: None,
+            wall_cooldown: Timer::from_seconds(BULLET_WALL_COOLDOWN, TimerMode::Once),
+            ghost: false,
         }
     }
+    /// Lets the bullet fly through walls, for no longer than a ghost bullet lives.
+    pub fn make_ghost(&mut self) {
+        self.ghost = true;
+        let max_age = self.lifetime.max_age.unwrap_or(GHOST_BULLET_LIFETIME);
+        self.lifetime.max_age = Some(max_age.min(GHOST_BULLET_LIFETIME));
+    }
+
     pub fn velocity(&self) -> Vec2 {
         let angle_rad = self.angle.to_radians();
         Vec2::new(angle_rad.cos(), angle_rad.sin()) * self.speed
     }
 }
+
+/// Match-wide firing limits every tank's `Gun` is built from.
+#[derive(Resource, Debug, Clone)]
+pub struct AmmoRules {
+    /// Bullets a tank may have flying at once. Every projectile of a shot
+    /// counts, and a shot only goes off if all of them fit.
+    pub max_live_bullets: usize,
+    /// Seconds between two shots
+    pub fire_cooldown: f32,
+    /// Magazine size and seconds to refill it once empty, `None` for no reloading.
+    pub magazine: Option<(u32, f32)>,
+    /// Off when shells only fly once the server has accepted the shot.
+    pub spawn_locally: bool,
+}
+
+impl Default for AmmoRules {
+    fn default() -> Self {
+        Self {
+            max_live_bullets: MAX_LIVE_BULLETS,
+            fire_cooldown: FIRE_COOLDOWN,
+            magazine: None,
+            spawn_locally: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Magazine {
+    pub size: u32,
+    pub rounds: u32,
+    pub reload: Timer,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Gun {
+    pub max_live_bullets: usize,
+    pub cooldown: Timer,
+    pub magazine: Option<Magazine>,
+}
+
+impl Gun {
+    pub fn new(rules: &AmmoRules) -> Self {
+        let mut cooldown = Timer::from_seconds(rules.fire_cooldown, TimerMode::Once);
+        // Ready to fire straight away
+        cooldown.tick(Duration::from_secs_f32(rules.fire_cooldown));
+        Self {
+            max_live_bullets: rules.max_live_bullets,
+            cooldown,
+            magazine: rules.magazine.map(|(size, reload)| Magazine {
+                size,
+                rounds: size,
+                reload: Timer::from_seconds(reload, TimerMode::Once),
+            }),
+        }
+    }
+
+    /// Whether a shot of `projectiles` bullets may go off next to the `live_bullets` still flying.
+    pub fn can_fire(&self, live_bullets: usize, projectiles: usize) -> bool {
+        let loaded = self
+            .magazine
+            .as_ref()
+            .is_none_or(|magazine| magazine.rounds > 0);
+        live_bullets + projectiles <= self.max_live_bullets && self.cooldown.finished() && loaded
+    }
+
+    /// Uses up a shot and waits `cooldown` seconds before the next one.
+    fn fire(&mut self, cooldown: f32) {
+        self.cooldown
+            .set_duration(Duration::from_secs_f32(cooldown));
+        self.cooldown.reset();
+        if let Some(magazine) = self.magazine.as_mut() {
+            magazine.rounds = magazine.rounds.saturating_sub(1);
+            if magazine.rounds == 0 {
+                magazine.reload.reset();
+            }
+        }
+    }
+
+    /// Shots of `projectiles` bullets available right now, ignoring the cooldown.
+    pub fn shots_left(&self, live_bullets: usize, projectiles: usize) -> usize {
+        let free = self.max_live_bullets.saturating_sub(live_bullets) / projectiles;
+        match &self.magazine {
+            Some(magazine) => free.min(magazine.rounds as usize),
+            None => free,
+        }
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.magazine
+            .as_ref()
+            .is_some_and(|magazine| magazine.rounds == 0)
+    }
+}
+
+/// Sent whenever a tank fires bullets, so the network layer can report the
+/// shot. Ray weapons send `LaserFired` instead.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BulletFired {
+    pub owner: Entity,
+    pub weapon: WeaponKind,
+    pub position: Vec2,
+    /// Angle in degrees
+    pub angle: f32,
+}
+
 pub struct BulletPlugin;
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (shooting_system, bullet_velocity_system));
+        app.init_resource::<AmmoRules>()
+            .init_resource::<BulletLifetime>()
+            .add_event::<BulletFired>()
+            .add_event::<BulletExpired>()
+            .add_systems(
+                Update,
+                (
+                    gun_system,
+                    shooting_system,
+                    bullet_velocity_system,
+                    bullet_lifetime_system,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn gun_system(time: Res<Time>, mut query: Query<&mut Gun>) {
+    for mut gun in query.iter_mut() {
+        gun.cooldown.tick(time.delta());
+        if let Some(magazine) = gun.magazine.as_mut() {
+            if magazine.rounds == 0 && magazine.reload.tick(time.delta()).finished() {
+                magazine.rounds = magazine.size;
+            }
+        }
     }
 }
 
+/// Bullets `owner` fired that count against its ammo; shrapnel from its frag
+/// rounds doesn't.
+pub fn live_bullets(owner: Entity, bullets: &Query<&Bullet>) -> usize {
+    bullets
+        .iter()
+        .filter(|bullet| bullet.owner == owner && bullet.weapon != WeaponKind::Shrapnel)
+        .count()
+}
+
 fn bullet_velocity_system(mut query: Query<(&mut Velocity, &Bullet)>) {
     for (mut velocity, bullet) in query.iter_mut() {
         let x_modifier = bullet.angle.to_radians().cos();
@@ -48,37 +297,127 @@ fn bullet_velocity_system(mut query: Query<(&mut Velocity, &Bullet)>) {
     }
 }
 
-fn shooting_system(
+fn bullet_lifetime_system(
     mut commands: Commands,
-    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    query: Query<&Transform, With<Tank>>,
+    mut query: Query<(
+        Entity,
+        &mut Bullet,
+        &Transform,
+        &MeshMaterial2d<ColorMaterial>,
+    )>,
+    mut expired_events: EventWriter<BulletExpired>,
 ) {
-    if !keys.just_pressed(KeyCode::Space) {
-        return;
+    for (entity, mut bullet, transform, material) in query.iter_mut() {
+        bullet.age += time.delta_secs();
+
+        if let Some(reason) = bullet.lifetime.expiry(bullet.age, bullet.bounce_count) {
+            expired_events.send(BulletExpired {
+                bullet: entity,
+                owner: bullet.owner,
+                position: transform.translation.xy(),
+                weapon: bullet.weapon,
+                reason,
+            });
+            commands.entity(entity).try_despawn();
+            continue;
+        }
+
+        if let Some(material) = materials.get_mut(&material.0) {
+            material
+                .color
+                .set_alpha(bullet.lifetime.opacity(bullet.age));
+        }
     }
+}
 
-    let transform = query.single();
+/// What `shooting_system` needs to know about the tank that fires.
+type Shooter<'a> = (
+    Entity,
+    &'a TankInput,
+    &'a Transform,
+    &'a mut Gun,
+    &'a EquippedWeapon,
+    &'a PowerUps,
+    Option<&'a Children>,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn shooting_system(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<Shooter, With<Tank>>,
+    (bullet_query, turret_query): (Query<&Bullet>, Query<&Turret>),
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    (ammo_rules, lifetime): (Res<AmmoRules>, Res<BulletLifetime>),
+    (mut fired_events, mut laser_events): (EventWriter<BulletFired>, EventWriter<LaserFired>),
+) {
+    for (tank, input, transform, mut gun, weapon, power_ups, children) in query.iter_mut() {
+        if !input.fire {
+            continue;
+        }
+        let Some(def) = armory.get(weapon.0, &weapon_assets) else {
+            continue;
+        };
+        if !gun.can_fire(live_bullets(tank, &bullet_query), def.projectiles()) {
+            continue;
+        }
+        // The match cooldown is a floor, weapons can only be slower
+        gun.fire(def.fire_cooldown.max(ammo_rules.fire_cooldown));
 
-    let rotate = transform.rotation.to_euler(EulerRot::XYZ).2;
+        let (position, angle) = muzzle(transform, turret_angle(children, &turret_query));
+        // Beams hit straight away, they leave no bullet behind
+        if let ProjectileBehavior::Ray { .. } = def.behavior {
+            laser_events.send(LaserFired {
+                owner: tank,
+                weapon: weapon.0,
+                origin: position,
+                angle,
+            });
+            continue;
+        }
+        fired_events.send(BulletFired {
+            owner: tank,
+            weapon: weapon.0,
+            position,
+            angle,
+        });
+        if !ammo_rules.spawn_locally {
+            continue;
+        }
 
-    let x_modifier = rotate.cos();
-    let y_modifier = rotate.sin();
-    let tank_x = transform.translation.x;
-    let tank_y = transform.translation.y;
+        let ghost = power_ups.has(PowerUp::GhostBullets);
+        for angle in def.projectile_angles(angle) {
+            let mut bullet = Bullet::new(angle, tank, weapon.0, def, def.lifetime(*lifetime));
+            if ghost {
+                bullet.make_ghost();
+            }
+            spawn_bullet(&mut commands, &mut meshes, &mut materials, position, bullet);
+        }
+    }
+}
 
-    let (bullet_x, bullet_y) = (
-        tank_x + BULLET_OFFSET * x_modifier,
-        tank_y + BULLET_OFFSET * y_modifier,
-    );
-    let center = (bullet_x, bullet_y, 0.).into();
+/// Where a tank's shots leave its barrel and the angle in degrees they fly at,
+/// given the tank's `transform` and its turret's angle relative to the hull.
+pub fn muzzle(transform: &Transform, turret_angle: f32) -> (Vec2, f32) {
+    let rotate = transform.rotation.to_euler(EulerRot::XYZ).2 + turret_angle.to_radians();
+    let position = transform.translation.xy() + Vec2::from_angle(rotate) * BULLET_OFFSET;
+    (position, rotate.to_degrees())
+}
 
+pub fn spawn_bullet(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec2,
+    bullet: Bullet,
+) -> Entity {
     let aabb = Aabb {
-        center,
-        half_extents: BULLET_HALF_EXTENTS.into(),
+        center: position.extend(0.).into(),
+        half_extents: Vec3::new(bullet.size / 2., bullet.size / 2., 0.).into(),
     };
-    let bullet = Bullet::new(rotate.to_degrees());
     commands
         //.spawn(ColorMesh2dBundle {
         //    mesh: meshes.add(Circle::new(BULLET_SIZE)).into(),
@@ -86,14 +425,15 @@ fn shooting_system(
         //    ..default()
         //})
         .spawn((
-            Mesh2d(meshes.add(Circle::new(BULLET_SIZE))),
+            Mesh2d(meshes.add(Circle::new(bullet.size))),
             MeshMaterial2d(
                 materials.add(ColorMaterial::from_color(Color::srgb(250.0, 50.0, 50.0))),
             ),
         ))
-        .insert(Transform::from_xyz(bullet_x, bullet_y, 0.))
+        .insert(Transform::from_xyz(position.x, position.y, 0.))
         .insert(Velocity { x: 0., y: 0. })
         .insert(bullet)
         .insert(Collider::Aabb(aabb))
-        .insert(Dynamic);
+        .insert(Dynamic)
+        .id()
 }
@@ -1,92 +1,294 @@
-use bevy::prelude::*;
+use bevy::{color::palettes::css, prelude::*};
 use bevy_rapier2d::prelude::*;
 
-use crate::constants::{GAME_FIELD_HEIGHT, GAME_FIELD_WIDTH, GRID_CELL_SIZE};
-pub const TANK_LENGTH: f32 = 60.;
-pub const TANK_WIDTH: f32 = 40.;
-pub const TANK_ROTATION_SPEED: f32 = 90.0;
+use crate::{
+    collider::{self, Obb},
+    constants::BULLET_OFFSET,
+};
+
+use super::{
+    bots::Bot,
+    classes::{Loadout, TankClass, TankClassDef, TankClasses},
+    collision::Dynamic,
+    colors::PlayerColor,
+    combat::Health,
+    game_flow::{driven_locally, AppState},
+    handling::{TankStats, TankStatsLoader},
+    input::{Controls, TankInput},
+    modes::{ModeRounds, ModeRules},
+    pickups::{PowerUp, PowerUps, SPEED_BOOST_MULTIPLIER},
+    players::{LocalPlayers, Player},
+    shooting::{AmmoRules, Gun},
+    weapons::EquippedWeapon,
+};
+
+pub const TURRET_ROTATION_SPEED: f32 = 180.;
+const TURRET_RADIUS: f32 = 10.;
+const BARREL_WIDTH: f32 = 6.;
+/// A tank played on another client, put wherever the server last saw it.
+/// That client decides when it is hit or pushed, so it carries no `Health`
+/// here and nothing moves it locally.
+#[derive(Component, Debug)]
+pub struct Remote;
 
-pub const TANK_X_HALF_EXTENT: f32 = TANK_LENGTH / 2.;
-pub const TANK_Y_HALF_EXTENT: f32 = TANK_WIDTH / 2.;
-pub const TANK_SPEED: f32 = 150.;
-pub const TANK_SIZE: (f32, f32) = (60., 40.);
 #[derive(Component, Default)]
 pub struct Tank {
-    pub rotation_speed: f32,
-    pub speed: f32,
+    pub class: TankClass,
+    pub stats: Handle<TankStats>,
+    /// Hull length and width
+    pub size: Vec2,
 }
 
 impl Tank {
-    pub fn size() -> Vec2 {
-        TANK_SIZE.into()
-    }
-    pub fn new() -> Self {
+    pub fn new(class: TankClass, def: &TankClassDef) -> Self {
         Self {
-            speed: TANK_SPEED,
-            rotation_speed: TANK_ROTATION_SPEED,
+            class,
+            stats: def.stats.clone(),
+            size: def.size,
         }
     }
-    pub fn half_extents() -> Vec2 {
-        (TANK_LENGTH / 2.0, TANK_WIDTH / 2.0).into()
+    /// The tank's handling, or the defaults while its file is still loading.
+    pub fn stats<'a>(&self, assets: &'a Assets<TankStats>) -> &'a TankStats {
+        assets.get(&self.stats).unwrap_or(&TankStats::DEFAULT)
+    }
+    pub fn half_extents(&self) -> Vec2 {
+        self.size / 2.0
     }
 }
 
+/// Gun mounted on a tank as a child entity, turning on its own.
+#[derive(Component, Debug)]
+pub struct Turret {
+    pub rotation_speed: f32,
+    /// Degrees relative to the hull
+    pub angle: f32,
+}
+
+/// How tanks aim their shots. Shared by every tank here, any player's
+/// `Action::ToggleAim` switches it.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AimMode {
+    /// The turret is locked forward, shots go where the hull faces
+    #[default]
+    Hull,
+    /// The turret follows each player's aim, the mouse or a right stick
+    Turret,
+}
+
 pub struct TankPlugin;
 impl Plugin for TankPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup)
-            .add_systems(Update, tank_movement_system);
+        app.init_asset::<TankStats>()
+            .init_asset_loader::<TankStatsLoader>()
+            .init_resource::<AimMode>()
+            .add_systems(
+                Update,
+                (
+                    spawn_tank_system.run_if(in_state(AppState::Countdown).and(driven_locally)),
+                    tank_movement_system,
+                    toggle_aim_mode_system,
+                    turret_aim_system,
+                ),
+            );
     }
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let center = (
-        -GAME_FIELD_WIDTH / 2. + GRID_CELL_SIZE / 2.,
-        GAME_FIELD_HEIGHT / 2. - GRID_CELL_SIZE / 2.,
-    );
+/// Angle in degrees of a tank's turret relative to its hull, 0 without one.
+pub fn turret_angle(children: Option<&Children>, turrets: &Query<&Turret>) -> f32 {
+    children
+        .into_iter()
+        .flatten()
+        .find_map(|child| turrets.get(*child).ok())
+        .map_or(0.0, |turret| turret.angle)
+}
+
+/// Puts every local player's tank where the game mode starts them during the
+/// countdown, waiting on any class that hasn't loaded yet.
+fn spawn_tank_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    (classes, class_assets, local_players): (
+        Res<TankClasses>,
+        Res<Assets<TankClassDef>>,
+        Res<LocalPlayers>,
+    ),
+    (ammo_rules, mode_rules): (Res<AmmoRules>, Res<ModeRules>),
+    player_query: Query<&Player, With<Tank>>,
+) {
+    for (index, slot) in local_players.0.iter().enumerate() {
+        if player_query.iter().any(|player| player.0 == index) {
+            continue;
+        }
+        let Some(def) = classes.get(slot.class, &class_assets) else {
+            continue;
+        };
+
+        let tank = spawn_tank(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &ammo_rules,
+            slot.class,
+            def,
+            Transform::from_translation(mode_rules.mode.spawn_point(index).extend(0.)),
+        );
+        let mut tank = commands.entity(tank);
+        tank.insert((
+            Player(index),
+            slot.controls.clone(),
+            PlayerColor(index as u8),
+        ));
+        if let Some(team) = mode_rules.mode.team(index) {
+            tank.insert(team);
+        }
+        if let Controls::Bot(difficulty) = slot.controls {
+            tank.insert(Bot::new(difficulty));
+        }
+    }
+}
+
+pub fn spawn_tank(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    ammo_rules: &AmmoRules,
+    class: TankClass,
+    def: &TankClassDef,
+    transform: Transform,
+) -> Entity {
+    let tank = Tank::new(class, def);
+    let half_extents = tank.half_extents();
     commands
         .spawn((
             Sprite {
-                image: asset_server.load("tank.png").into(),
-                custom_size: Some(Tank::size()),
+                image: def.sprite.clone(),
+                custom_size: Some(def.size),
                 ..default()
             },
-            RigidBody::Dynamic,
+            // Moved by its velocity only, the collision systems keep it out
+            // of walls and other tanks
+            RigidBody::KinematicVelocityBased,
         ))
-        .insert(Transform::from_xyz(center.0, center.1, 0.))
+        .insert(transform)
         .insert(GravityScale(0.))
         .insert(KinematicCharacterController { ..default() })
         .insert(Velocity {
             linvel: Vec2::ZERO,
             angvel: 1.,
         })
-        .insert(Collider::cuboid(TANK_X_HALF_EXTENT, TANK_Y_HALF_EXTENT))
-        .insert(Tank::new());
+        .insert(Collider::cuboid(half_extents.x, half_extents.y))
+        .insert(collider::Collider::Obb(Obb {
+            center: transform.translation.xy(),
+            half_extents,
+            rotation: transform.rotation.to_euler(EulerRot::XYZ).2.to_degrees(),
+        }))
+        .insert(Dynamic)
+        .insert(Health::new(def.health))
+        .insert(Gun::new(ammo_rules))
+        .insert(EquippedWeapon(def.loadout[0]))
+        .insert(Loadout(def.loadout.clone()))
+        .insert(PowerUps::default())
+        .insert(TankInput::default())
+        .insert(tank)
+        .with_children(|parent| {
+            let material = materials.add(Color::from(css::DARK_OLIVEGREEN));
+            parent
+                .spawn((
+                    Mesh2d(meshes.add(Circle::new(TURRET_RADIUS))),
+                    MeshMaterial2d(material.clone()),
+                    Transform::from_xyz(0., 0., 0.2),
+                    Turret {
+                        rotation_speed: TURRET_ROTATION_SPEED,
+                        angle: 0.,
+                    },
+                ))
+                .with_children(|turret| {
+                    turret.spawn((
+                        Mesh2d(meshes.add(Rectangle::new(BULLET_OFFSET, BARREL_WIDTH))),
+                        MeshMaterial2d(material),
+                        Transform::from_xyz(BULLET_OFFSET / 2., 0., -0.1),
+                    ));
+                });
+        })
+        .id()
 }
-fn tank_movement_system(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&Tank, &mut Transform, &mut Velocity)>,
+
+fn toggle_aim_mode_system(input_query: Query<&TankInput>, mut aim_mode: ResMut<AimMode>) {
+    if input_query.iter().any(|input| input.toggle_aim) {
+        *aim_mode = match *aim_mode {
+            AimMode::Hull => AimMode::Turret,
+            AimMode::Turret => AimMode::Hull,
+        };
+    }
+}
+
+fn turret_aim_system(
+    time: Res<Time>,
+    aim_mode: Res<AimMode>,
+    tank_query: Query<(&Transform, &TankInput, &Children), With<Tank>>,
+    mut turret_query: Query<(&mut Turret, &mut Transform), Without<Tank>>,
 ) {
-    let (tank, transform, mut velocity) = query.single_mut();
-
-    if keyboard_input.pressed(KeyCode::KeyA) {
-        velocity.angvel = tank.rotation_speed.to_radians();
-    } else if keyboard_input.pressed(KeyCode::KeyD) {
-        velocity.angvel = -tank.rotation_speed.to_radians();
-    } else {
-        velocity.angvel = 0.0;
+    for (tank_transform, input, children) in tank_query.iter() {
+        let Some(&turret_entity) = children.iter().find(|child| turret_query.contains(**child))
+        else {
+            continue;
+        };
+        let Ok((mut turret, mut transform)) = turret_query.get_mut(turret_entity) else {
+            continue;
+        };
+
+        let hull_angle = tank_transform
+            .rotation
+            .to_euler(EulerRot::XYZ)
+            .2
+            .to_degrees();
+        let target = match *aim_mode {
+            AimMode::Hull => Some(0.0),
+            AimMode::Turret => input.aim.map(|target| {
+                let to_target = target - tank_transform.translation.xy();
+                to_target.y.atan2(to_target.x).to_degrees() - hull_angle
+            }),
+        };
+
+        if let Some(target) = target {
+            // Shortest signed turn in (-180, 180]
+            let turn = (target - turret.angle + 540.0).rem_euclid(360.0) - 180.0;
+            let max_turn = turret.rotation_speed * time.delta_secs();
+            turret.angle = (turret.angle + turn.clamp(-max_turn, max_turn)).rem_euclid(360.0);
+        }
+        transform.rotation = Quat::from_rotation_z(turret.angle.to_radians());
     }
+}
 
-    let angle = transform.rotation.to_euler(EulerRot::XYZ).2;
-    let direction = Vec2::new(angle.cos(), angle.sin());
+fn tank_movement_system(
+    time: Res<Time>,
+    stats_assets: Res<Assets<TankStats>>,
+    mut query: Query<(
+        &Tank,
+        &TankInput,
+        &Transform,
+        &mut Velocity,
+        Option<&PowerUps>,
+    )>,
+) {
+    for (tank, input, transform, mut velocity, power_ups) in query.iter_mut() {
+        let stats = tank.stats(&stats_assets);
+        let speed_multiplier =
+            if power_ups.is_some_and(|power_ups| power_ups.has(PowerUp::SpeedBoost)) {
+                SPEED_BOOST_MULTIPLIER
+            } else {
+                1.0
+            };
 
-    let movement = if keyboard_input.pressed(KeyCode::KeyW) {
-        tank.speed
-    } else if keyboard_input.pressed(KeyCode::KeyS) {
-        -tank.speed
-    } else {
-        0.0
-    };
+        let angle = transform.rotation.to_euler(EulerRot::XYZ).2;
+        let direction = Vec2::new(angle.cos(), angle.sin());
+        // Start from how fast the physics actually moved the tank, so bumping
+        // into a wall or another tank eats the momentum.
+        let speed = velocity.linvel.dot(direction);
+        let speed = stats.accelerate(speed, input.throttle, speed_multiplier, time.delta_secs());
 
-    velocity.linvel = direction * movement;
+        velocity.angvel = input.steer * stats.turn_rate(speed).to_radians();
+        velocity.linvel = direction * speed;
+    }
 }
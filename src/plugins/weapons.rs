@@ -0,0 +1,368 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    color::palettes::css,
+    prelude::*,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+pub use shared::weapons::WeaponKind;
+
+use crate::{collider::Collider, walls::Wall};
+
+use super::{
+    classes::Loadout,
+    collision::cast_beam,
+    combat::Damage,
+    input::TankInput,
+    modes::{ModeRules, Team},
+    shooting::{muzzle, spawn_bullet, Bullet, BulletExpired, BulletLifetime},
+    tank::{turret_angle, Tank, Turret},
+};
+
+/// Seconds a laser beam stays on screen after firing.
+const LASER_BEAM_LIFETIME: f32 = 0.3;
+/// Opacity of the aiming line drawn while a ray weapon is equipped.
+const LASER_PREVIEW_ALPHA: f32 = 0.25;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProjectileBehavior {
+    /// Plain shell bouncing around the maze
+    Ballistic,
+    /// Instant beam traced through the maze up to `range` long
+    Ray { range: f32 },
+    /// Bursts into `fragments` shells when it expires
+    Frag { fragments: u32 },
+    /// Steers towards the closest enemy tank by up to `turn_rate` degrees per second
+    Guided { turn_rate: f32 },
+}
+
+/// A weapon as written in its `*.weapon.ron` file.
+#[derive(Asset, TypePath, Debug, Clone, Serialize, Deserialize)]
+pub struct WeaponDef {
+    pub name: String,
+    pub behavior: ProjectileBehavior,
+    pub speed: f32,
+    pub size: f32,
+    pub damage: f32,
+    /// Overrides the match's bounce limit when set; for rays, the number of reflections.
+    pub max_bounces: Option<u8>,
+    /// Overrides the match's bullet lifetime in seconds when set.
+    pub max_age: Option<f32>,
+    /// Cone in degrees the projectiles are fanned across
+    pub spread: f32,
+    /// Seconds between shots
+    pub fire_cooldown: f32,
+    /// Projectiles fired per shot, each counting against `AmmoRules::max_live_bullets`
+    pub projectile_count: u32,
+}
+
+impl WeaponDef {
+    /// The match-wide `lifetime` with this weapon's overrides applied.
+    pub fn lifetime(&self, lifetime: BulletLifetime) -> BulletLifetime {
+        BulletLifetime {
+            max_age: self.max_age.or(lifetime.max_age),
+            max_bounces: self.max_bounces.or(lifetime.max_bounces),
+            ..lifetime
+        }
+    }
+
+    /// Live bullets one shot adds, and so needs room for.
+    pub fn projectiles(&self) -> usize {
+        self.projectile_count.max(1) as usize
+    }
+
+    /// Angles in degrees of each projectile fired towards `angle`.
+    pub fn projectile_angles(&self, angle: f32) -> impl Iterator<Item = f32> + '_ {
+        let count = self.projectile_count.max(1);
+        let step = if count > 1 {
+            self.spread / (count - 1) as f32
+        } else {
+            0.0
+        };
+        let first = angle - step * (count - 1) as f32 / 2.0;
+        (0..count).map(move |i| first + step * i as f32)
+    }
+}
+
+#[derive(Default)]
+pub struct WeaponDefLoader;
+
+impl AssetLoader for WeaponDefLoader {
+    type Asset = WeaponDef;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["weapon.ron"]
+    }
+}
+
+/// Every weapon definition in the game, keyed by kind and loaded on startup.
+#[derive(Resource, Debug, Default)]
+pub struct Armory(pub HashMap<WeaponKind, Handle<WeaponDef>>);
+
+impl Armory {
+    pub fn get<'a>(
+        &self,
+        kind: WeaponKind,
+        assets: &'a Assets<WeaponDef>,
+    ) -> Option<&'a WeaponDef> {
+        assets.get(self.0.get(&kind)?)
+    }
+}
+
+/// The weapon a tank fires.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct EquippedWeapon(pub WeaponKind);
+
+/// Sent by `shooting_system` for `Ray` weapons instead of spawning bullets.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LaserFired {
+    pub owner: Entity,
+    pub weapon: WeaponKind,
+    pub origin: Vec2,
+    /// Angle in degrees
+    pub angle: f32,
+}
+
+#[derive(Component)]
+struct LaserBeam {
+    points: Vec<Vec2>,
+    timer: Timer,
+}
+
+pub struct WeaponsPlugin;
+impl Plugin for WeaponsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WeaponDef>()
+            .init_asset_loader::<WeaponDefLoader>()
+            .init_resource::<Armory>()
+            .add_event::<LaserFired>()
+            .add_systems(PreStartup, load_weapons)
+            .add_systems(
+                Update,
+                (
+                    switch_weapon_system,
+                    guided_system,
+                    frag_system,
+                    laser_system,
+                    laser_beam_system,
+                    laser_preview_system,
+                ),
+            );
+    }
+}
+
+fn load_weapons(asset_server: Res<AssetServer>, mut armory: ResMut<Armory>) {
+    for kind in WeaponKind::ALL {
+        armory.0.insert(kind, asset_server.load(kind.path()));
+    }
+}
+
+fn switch_weapon_system(
+    mut query: Query<(&TankInput, &mut EquippedWeapon, Option<&Loadout>), With<Tank>>,
+) {
+    for (input, mut weapon, loadout) in query.iter_mut() {
+        if !input.switch_weapon {
+            continue;
+        }
+        weapon.0 = match loadout {
+            Some(loadout) => loadout.next(weapon.0),
+            None => weapon.0.next(),
+        };
+    }
+}
+
+/// Turns guided bullets towards the closest tank their owner may hurt, so
+/// they leave teammates alone unless friendly fire is on.
+fn guided_system(
+    time: Res<Time>,
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    mode_rules: Res<ModeRules>,
+    mut bullet_query: Query<(&mut Bullet, &Transform)>,
+    tank_query: Query<(Entity, &Transform, Option<&Team>), With<Tank>>,
+) {
+    for (mut bullet, transform) in bullet_query.iter_mut() {
+        let Some(ProjectileBehavior::Guided { turn_rate }) = armory
+            .get(bullet.weapon, &weapon_assets)
+            .map(|def| &def.behavior)
+        else {
+            continue;
+        };
+        let position = transform.translation.xy();
+        let owner_team = tank_query
+            .get(bullet.owner)
+            .ok()
+            .and_then(|(_, _, team)| team.copied());
+
+        let target = tank_query
+            .iter()
+            .filter(|(tank, _, team)| {
+                *tank != bullet.owner && mode_rules.can_damage(owner_team, team.copied())
+            })
+            .map(|(_, tank_transform, _)| tank_transform.translation.xy())
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            });
+        let Some(target) = target else {
+            continue;
+        };
+
+        let to_target = target - position;
+        let desired = to_target.y.atan2(to_target.x).to_degrees();
+        // Shortest signed turn in (-180, 180]
+        let turn = (desired - bullet.angle + 540.0).rem_euclid(360.0) - 180.0;
+        let max_turn = *turn_rate * time.delta_secs();
+        bullet.angle += turn.clamp(-max_turn, max_turn);
+    }
+}
+
+fn frag_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    match_lifetime: Res<BulletLifetime>,
+    mut expired_events: EventReader<BulletExpired>,
+) {
+    let Some(shrapnel) = armory.get(WeaponKind::Shrapnel, &weapon_assets) else {
+        return;
+    };
+    let lifetime = shrapnel.lifetime(*match_lifetime);
+    for event in expired_events.read() {
+        let Some(ProjectileBehavior::Frag { fragments }) = armory
+            .get(event.weapon, &weapon_assets)
+            .map(|def| &def.behavior)
+        else {
+            continue;
+        };
+
+        for i in 0..*fragments {
+            let angle = 360.0 * i as f32 / *fragments as f32;
+            let mut bullet =
+                Bullet::new(angle, event.owner, WeaponKind::Shrapnel, shrapnel, lifetime);
+            bullet.left_owner = true;
+            spawn_bullet(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                event.position,
+                bullet,
+            );
+        }
+    }
+}
+
+fn laser_system(
+    mut commands: Commands,
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    match_lifetime: Res<BulletLifetime>,
+    mut laser_events: EventReader<LaserFired>,
+    wall_query: Query<&Collider, With<Wall>>,
+    tank_query: Query<(Entity, &Collider), With<Tank>>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    let walls: Vec<&Collider> = wall_query.iter().collect();
+    let tanks: Vec<(Entity, &Collider)> = tank_query.iter().collect();
+
+    for event in laser_events.read() {
+        let Some(def) = armory.get(event.weapon, &weapon_assets) else {
+            continue;
+        };
+        let ProjectileBehavior::Ray { range } = def.behavior else {
+            continue;
+        };
+        let bounces = def.lifetime(*match_lifetime).max_bounces.unwrap_or(0);
+        let (points, hit) = cast_beam(
+            event.origin,
+            Vec2::from_angle(event.angle.to_radians()),
+            bounces as u32,
+            range,
+            &walls,
+            &tanks,
+            event.owner,
+        );
+
+        if let Some(tank) = hit {
+            damage_events.send(Damage {
+                target: tank,
+                source: event.owner,
+                amount: def.damage,
+            });
+        }
+
+        commands.spawn(LaserBeam {
+            points,
+            timer: Timer::from_seconds(LASER_BEAM_LIFETIME, TimerMode::Once),
+        });
+    }
+}
+
+fn laser_beam_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+    mut query: Query<(Entity, &mut LaserBeam)>,
+) {
+    for (entity, mut beam) in query.iter_mut() {
+        if beam.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let color = Color::from(css::AQUA).with_alpha(beam.timer.fraction_remaining());
+        gizmos.linestrip_2d(beam.points.iter().copied(), color);
+    }
+}
+
+/// Shows where each tank's ray weapon would hit if fired now.
+fn laser_preview_system(
+    mut gizmos: Gizmos,
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    match_lifetime: Res<BulletLifetime>,
+    shooter_query: Query<(Entity, &Transform, &EquippedWeapon, Option<&Children>), With<Tank>>,
+    turret_query: Query<&Turret>,
+    wall_query: Query<&Collider, With<Wall>>,
+    tank_query: Query<(Entity, &Collider), With<Tank>>,
+) {
+    let walls: Vec<&Collider> = wall_query.iter().collect();
+    let tanks: Vec<(Entity, &Collider)> = tank_query.iter().collect();
+
+    for (shooter, transform, weapon, children) in shooter_query.iter() {
+        let Some(def) = armory.get(weapon.0, &weapon_assets) else {
+            continue;
+        };
+        let ProjectileBehavior::Ray { range } = def.behavior else {
+            continue;
+        };
+
+        let bounces = def.lifetime(*match_lifetime).max_bounces.unwrap_or(0);
+        let (origin, angle) = muzzle(transform, turret_angle(children, &turret_query));
+        let (points, hit) = cast_beam(
+            origin,
+            Vec2::from_angle(angle.to_radians()),
+            bounces as u32,
+            range,
+            &walls,
+            &tanks,
+            shooter,
+        );
+
+        let color = Color::from(css::AQUA).with_alpha(LASER_PREVIEW_ALPHA);
+        gizmos.linestrip_2d(points.iter().copied(), color);
+        if let (Some(_), Some(end)) = (hit, points.last()) {
+            gizmos.circle_2d(Isometry2d::from_translation(*end), 6., css::RED);
+        }
+    }
+}
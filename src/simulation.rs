@@ -0,0 +1,527 @@
+use std::{collections::BTreeMap, fs, str::FromStr, time::Duration};
+
+use bevy::{
+    app::{AppExit, ScheduleRunnerPlugin},
+    gizmos::GizmoPlugin,
+    hierarchy::HierarchyPlugin,
+    input::InputPlugin as EngineInputPlugin,
+    prelude::*,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+    transform::TransformPlugin,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::Serialize;
+
+use crate::{
+    add_game_plugins,
+    plugins::{
+        bots::Difficulty,
+        classes::{TankClass, TankClassDef, TankClasses},
+        collision::BulletBounced,
+        combat::{Damage, TankDestroyed},
+        game_flow::{AppState, MatchRules, MatchScore, MazeRng},
+        handling::TankStats,
+        input::Controls,
+        pickups::PickupRules,
+        players::{LocalPlayers, Player, PlayerSlot, MAX_LOCAL_PLAYERS},
+        scoring::Scoreboard,
+        shooting::{Bullet, BulletExpired, BulletFired, ExpiryReason},
+        weapons::{Armory, LaserFired, WeaponDef, WeaponKind},
+    },
+    walls::MazeSeed,
+};
+
+/// Seconds every simulated frame moves the clock on, however long it took.
+const TIMESTEP: f64 = 1. / 60.;
+/// Real seconds to wait for the tank classes to load before giving up.
+pub const LOAD_TIMEOUT: f32 = 30.;
+/// Rounds between progress lines on the console.
+const PROGRESS_INTERVAL: usize = 100;
+
+/// What `--simulate` was asked to run.
+#[derive(Resource, Debug, Clone)]
+pub struct SimulationConfig {
+    pub rounds: usize,
+    /// Seeds the mazes, the same seed plays the same rounds
+    pub seed: u64,
+    /// Class and difficulty of every bot taking part
+    pub bots: Vec<(TankClass, Difficulty)>,
+    /// Seconds after which a round is called off as a draw
+    pub max_round_length: f32,
+    pub pickups: bool,
+    /// Results are written to `<output>.csv` and `<output>.json`
+    pub output: String,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            rounds: 1000,
+            seed: 0,
+            bots: vec![(TankClass::Medium, Difficulty::Normal); 2],
+            max_round_length: 120.,
+            pickups: false,
+            output: String::from("simulation"),
+        }
+    }
+}
+
+/// Parses `value` given for `flag`, keeping `default` if it is missing or bad.
+pub fn parse_or<T: FromStr>(flag: &str, value: Option<&String>, default: T) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("ignoring {flag}: expected a value");
+            default
+        }
+    }
+}
+
+/// A tank class by name, like `heavy`.
+pub fn parse_class(name: &str) -> Option<TankClass> {
+    TankClass::ALL
+        .into_iter()
+        .find(|other| format!("{other:?}").eq_ignore_ascii_case(name))
+}
+
+/// One bot from `class[:difficulty]`, like `heavy:hard`.
+pub fn parse_bot(spec: &str) -> Option<(TankClass, Difficulty)> {
+    let (class, difficulty) = spec.split_once(':').unwrap_or((spec, "normal"));
+    let class = parse_class(class)?;
+    let difficulty = Difficulty::ALL
+        .into_iter()
+        .find(|other| other.name().eq_ignore_ascii_case(difficulty))?;
+    Some((class, difficulty))
+}
+
+impl SimulationConfig {
+    /// Reads `--simulate [rounds] [--seed N] [--bots class:difficulty,...]
+    /// [--max-round SECONDS] [--pickups] [--out NAME]` from the command line.
+    /// `None` when the game should open its window as usual.
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let start = args.iter().position(|arg| arg == "--simulate")?;
+        let mut config = Self::default();
+
+        let mut args = args[start + 1..].iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => config.seed = parse_or(arg, args.next(), config.seed),
+                "--max-round" => {
+                    config.max_round_length = parse_or(arg, args.next(), config.max_round_length)
+                }
+                "--pickups" => config.pickups = true,
+                "--out" => config.output = parse_or(arg, args.next(), config.output),
+                "--bots" => {
+                    let bots: Option<Vec<_>> = args
+                        .next()
+                        .map(|specs| specs.split(',').map(parse_bot).collect())
+                        .unwrap_or_default();
+                    match bots {
+                        Some(bots) if (2..=MAX_LOCAL_PLAYERS).contains(&bots.len()) => {
+                            config.bots = bots
+                        }
+                        _ => eprintln!(
+                            "ignoring --bots: expected 2 to {MAX_LOCAL_PLAYERS} of class:difficulty"
+                        ),
+                    }
+                }
+                other => match other.parse() {
+                    Ok(rounds) => config.rounds = rounds,
+                    Err(_) => eprintln!("ignoring unknown argument {other}"),
+                },
+            }
+        }
+        Some(config)
+    }
+}
+
+/// What happened in one simulated round, one line of the CSV.
+#[derive(Debug, Clone, Default)]
+struct RoundRecord {
+    round: u32,
+    maze_seed: u64,
+    /// `None` for a draw
+    winner: Option<String>,
+    /// Seconds of game time
+    length: f32,
+    timed_out: bool,
+    shots: u32,
+    hits: u32,
+    bounces: u32,
+    kills: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct BulletStats {
+    shots: u32,
+    /// Shots that damaged another tank
+    hits: u32,
+    bounces: u32,
+    /// Bullets that missed and timed out
+    expired_by_age: u32,
+    /// Bullets that missed and ran out of bounces
+    expired_by_bounces: u32,
+}
+
+#[derive(Resource, Debug, Default)]
+struct SimulationStats {
+    rounds: Vec<RoundRecord>,
+    /// The round being played
+    current: RoundRecord,
+    round_started: f32,
+    /// Over the whole run, by player name
+    bullets: BTreeMap<String, BulletStats>,
+}
+
+impl SimulationStats {
+    fn bullets(&mut self, player: &Player) -> &mut BulletStats {
+        self.bullets.entry(player.name()).or_default()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PlayerSummary {
+    name: String,
+    class: String,
+    difficulty: String,
+    wins: u32,
+    win_rate: f32,
+    kills: u32,
+    deaths: u32,
+    shots: u32,
+    hits: u32,
+    /// Hits per shot
+    accuracy: f32,
+    bounces: u32,
+    expired_by_age: u32,
+    expired_by_bounces: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    rounds: usize,
+    seed: u64,
+    draws: usize,
+    timeouts: usize,
+    /// Seconds of game time
+    average_round_length: f32,
+    average_shots_per_round: f32,
+    players: Vec<PlayerSummary>,
+}
+
+/// The whole game without a window, moving the clock on by `TIMESTEP`
+/// every update. Also what `gym` trains agents in.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+        TransformPlugin,
+        HierarchyPlugin,
+        EngineInputPlugin,
+        StatesPlugin,
+        AssetPlugin::default(),
+        GizmoPlugin,
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<ColorMaterial>()
+    .init_asset::<Image>()
+    // Every frame is the same length, so a run plays out the same way however
+    // fast it goes
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        TIMESTEP,
+    )));
+    add_game_plugins(&mut app);
+    app
+}
+
+/// Plays `config.rounds` bot-only rounds without a window, as fast as the
+/// machine allows, and writes what happened to CSV and JSON.
+pub fn run(config: SimulationConfig) {
+    let mut app = headless_app();
+    let slots = config
+        .bots
+        .iter()
+        .map(|(class, difficulty)| PlayerSlot {
+            controls: Controls::Bot(*difficulty),
+            class: *class,
+        })
+        .collect();
+    app.insert_resource(LocalPlayers(slots))
+        .insert_resource(MatchRules {
+            // Rounds go on until the run is over
+            rounds_to_win: u32::MAX,
+            countdown: 0.,
+            round_over: 0.,
+            driven_locally: true,
+        })
+        .insert_resource(MazeRng(StdRng::seed_from_u64(config.seed)))
+        .insert_resource(PickupRules {
+            spawn_locally: config.pickups,
+            ..default()
+        })
+        .insert_resource(config)
+        .init_resource::<SimulationStats>()
+        .add_systems(
+            Update,
+            start_when_loaded_system.run_if(in_state(AppState::MainMenu)),
+        )
+        .add_systems(OnEnter(AppState::InRound), round_start)
+        .add_systems(OnEnter(AppState::RoundOver), round_over)
+        .add_systems(
+            Update,
+            (
+                count_bullets_system,
+                round_timeout_system.run_if(in_state(AppState::InRound)),
+            ),
+        );
+    app.run();
+}
+
+/// Whether every tank class, its handling and every weapon has loaded.
+pub fn classes_loaded(
+    classes: &TankClasses,
+    class_assets: &Assets<TankClassDef>,
+    stats_assets: &Assets<TankStats>,
+    armory: &Armory,
+    weapon_assets: &Assets<WeaponDef>,
+) -> bool {
+    let classes_loaded = TankClass::ALL.into_iter().all(|class| {
+        classes
+            .get(class, class_assets)
+            .is_some_and(|def| stats_assets.contains(&def.stats))
+    });
+    classes_loaded
+        && WeaponKind::ALL
+            .into_iter()
+            .all(|kind| armory.get(kind, weapon_assets).is_some())
+}
+
+/// Skips the menus once every tank class, its handling and every weapon has
+/// loaded, so no round is played with stand-in stats.
+fn start_when_loaded_system(
+    real_time: Res<Time<Real>>,
+    (classes, class_assets, stats_assets): (
+        Res<TankClasses>,
+        Res<Assets<TankClassDef>>,
+        Res<Assets<TankStats>>,
+    ),
+    (armory, weapon_assets): (Res<Armory>, Res<Assets<WeaponDef>>),
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if classes_loaded(
+        &classes,
+        &class_assets,
+        &stats_assets,
+        &armory,
+        &weapon_assets,
+    ) {
+        next_state.set(AppState::Countdown);
+    } else if real_time.elapsed_secs() > LOAD_TIMEOUT {
+        eprintln!("tank classes or weapons did not load, is the assets folder next to the game?");
+        exit.send(AppExit::error());
+    }
+}
+
+fn round_start(time: Res<Time>, mut stats: ResMut<SimulationStats>) {
+    stats.current = RoundRecord::default();
+    stats.round_started = time.elapsed_secs();
+}
+
+fn count_bullets_system(
+    mut stats: ResMut<SimulationStats>,
+    (mut fired_events, mut laser_events): (EventReader<BulletFired>, EventReader<LaserFired>),
+    (mut bounce_events, mut expired_events): (
+        EventReader<BulletBounced>,
+        EventReader<BulletExpired>,
+    ),
+    (mut damage_events, mut destroyed_events): (EventReader<Damage>, EventReader<TankDestroyed>),
+    player_query: Query<&Player>,
+    bullet_query: Query<&Bullet>,
+) {
+    let shooters = fired_events
+        .read()
+        .map(|event| event.owner)
+        .chain(laser_events.read().map(|event| event.owner));
+    for owner in shooters {
+        stats.current.shots += 1;
+        if let Ok(player) = player_query.get(owner) {
+            stats.bullets(player).shots += 1;
+        }
+    }
+    for event in bounce_events.read() {
+        stats.current.bounces += 1;
+        let owner = bullet_query.get(event.bullet).map(|bullet| bullet.owner);
+        if let Ok(player) = owner.and_then(|owner| player_query.get(owner)) {
+            stats.bullets(player).bounces += 1;
+        }
+    }
+    for event in expired_events.read() {
+        let Ok(player) = player_query.get(event.owner) else {
+            continue;
+        };
+        let bullets = stats.bullets(player);
+        match event.reason {
+            ExpiryReason::Age => bullets.expired_by_age += 1,
+            ExpiryReason::Bounces => bullets.expired_by_bounces += 1,
+        }
+    }
+    for event in damage_events.read() {
+        if event.source == event.target {
+            continue;
+        }
+        stats.current.hits += 1;
+        if let Ok(player) = player_query.get(event.source) {
+            stats.bullets(player).hits += 1;
+        }
+    }
+    for event in destroyed_events.read() {
+        if event.killer_player.is_some() && event.killer_player != event.victim_player {
+            stats.current.kills += 1;
+        }
+    }
+}
+
+/// Calls off rounds where the bots never get to each other.
+fn round_timeout_system(
+    time: Res<Time>,
+    config: Res<SimulationConfig>,
+    mut stats: ResMut<SimulationStats>,
+    mut score: ResMut<MatchScore>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if time.elapsed_secs() - stats.round_started < config.max_round_length {
+        return;
+    }
+    stats.current.timed_out = true;
+    score.round_winner = None;
+    next_state.set(AppState::RoundOver);
+}
+
+fn round_over(
+    time: Res<Time>,
+    (config, score, seed): (Res<SimulationConfig>, Res<MatchScore>, Res<MazeSeed>),
+    scoreboard: Res<Scoreboard>,
+    mut stats: ResMut<SimulationStats>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let mut record = std::mem::take(&mut stats.current);
+    record.round = score.round;
+    record.maze_seed = seed.0;
+    record.winner = score.round_winner.clone();
+    record.length = time.elapsed_secs() - stats.round_started;
+    stats.rounds.push(record);
+
+    let played = stats.rounds.len();
+    if played.is_multiple_of(PROGRESS_INTERVAL) {
+        println!("{played}/{} rounds", config.rounds);
+    }
+    if played < config.rounds {
+        return;
+    }
+
+    let summary = summarize(&config, &stats, &scoreboard);
+    if let Err(err) = write_results(&config.output, &stats.rounds, &summary) {
+        eprintln!("could not write results: {err}");
+        exit.send(AppExit::error());
+        return;
+    }
+    for player in &summary.players {
+        println!(
+            "{} ({} {}): {:.1}% wins, {} kills, {:.1}% accuracy",
+            player.name,
+            player.class,
+            player.difficulty,
+            player.win_rate * 100.,
+            player.kills,
+            player.accuracy * 100.
+        );
+    }
+    println!(
+        "{} draws, {:.1}s average round, results in {}.csv and {}.json",
+        summary.draws, summary.average_round_length, config.output, config.output
+    );
+    exit.send(AppExit::Success);
+}
+
+fn ratio(part: u32, whole: usize) -> f32 {
+    if whole == 0 {
+        0.
+    } else {
+        part as f32 / whole as f32
+    }
+}
+
+fn summarize(
+    config: &SimulationConfig,
+    stats: &SimulationStats,
+    scoreboard: &Scoreboard,
+) -> Summary {
+    let rounds = stats.rounds.len();
+    let total = |field: fn(&RoundRecord) -> f32| stats.rounds.iter().map(field).sum::<f32>();
+
+    let players = config
+        .bots
+        .iter()
+        .enumerate()
+        .map(|(index, (class, difficulty))| {
+            let name = Player(index).name();
+            let score = scoreboard.0.get(&name).cloned().unwrap_or_default();
+            let bullets = stats.bullets.get(&name).cloned().unwrap_or_default();
+            PlayerSummary {
+                class: format!("{class:?}"),
+                difficulty: difficulty.name().to_string(),
+                wins: score.score,
+                win_rate: ratio(score.score, rounds),
+                kills: score.kills,
+                deaths: score.deaths,
+                shots: bullets.shots,
+                hits: bullets.hits,
+                accuracy: ratio(bullets.hits, bullets.shots as usize),
+                bounces: bullets.bounces,
+                expired_by_age: bullets.expired_by_age,
+                expired_by_bounces: bullets.expired_by_bounces,
+                name,
+            }
+        })
+        .collect();
+
+    Summary {
+        rounds,
+        seed: config.seed,
+        draws: stats
+            .rounds
+            .iter()
+            .filter(|round| round.winner.is_none())
+            .count(),
+        timeouts: stats.rounds.iter().filter(|round| round.timed_out).count(),
+        average_round_length: total(|round| round.length) / rounds.max(1) as f32,
+        average_shots_per_round: total(|round| round.shots as f32) / rounds.max(1) as f32,
+        players,
+    }
+}
+
+fn write_results(output: &str, rounds: &[RoundRecord], summary: &Summary) -> Result<(), String> {
+    let mut csv =
+        String::from("round,maze_seed,winner,length,timed_out,shots,hits,bounces,kills\n");
+    for round in rounds {
+        csv += &format!(
+            "{},{},{},{:.3},{},{},{},{},{}\n",
+            round.round,
+            round.maze_seed,
+            round.winner.as_deref().unwrap_or(""),
+            round.length,
+            round.timed_out,
+            round.shots,
+            round.hits,
+            round.bounces,
+            round.kills
+        );
+    }
+    fs::write(format!("{output}.csv"), csv).map_err(|err| err.to_string())?;
+
+    let json = serde_json::to_string_pretty(summary).map_err(|err| err.to_string())?;
+    fs::write(format!("{output}.json"), json).map_err(|err| err.to_string())
+}
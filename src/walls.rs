@@ -1,23 +1,29 @@
-use bevy::{prelude::*, render::primitives::Aabb, utils::HashSet};
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::primitives::Aabb,
+    utils::{hashbrown::hash_map::Entry, HashMap, HashSet},
+};
 use bevy_rapier2d::prelude::*;
-use rand::seq::SliceRandom;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+pub use shared::deployables::WallType;
 
 const H_WALL_HALF_SIZE: (f32, f32, f32) = (GRID_CELL_SIZE / 2., 2.5, 0.);
 const V_WALL_HALF_SIZE: (f32, f32, f32) = (2.5, GRID_CELL_SIZE / 2., 0.);
 
 use crate::{
-    constants::{
-        GAME_FIELD_HEIGHT, GAME_FIELD_WIDTH, GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_SIZE,
-        GRID_CELL_VERTICAL_AMOUNT,
-    },
+    collider,
+    constants::{GRID_CELL_HORIZONTAL_AMOUNT, GRID_CELL_SIZE, GRID_CELL_VERTICAL_AMOUNT},
+    grid::{cell_center, WallGrid},
     plugins::collision::Static,
 };
 
-#[derive(Clone)]
-pub enum WallType {
-    Horizontal,
-    Vertical,
-}
+/// Seed the next maze is generated from. Every client in a networked match
+/// gets the same one from the server, so they all build the same maze.
+#[derive(Resource, Debug, Default)]
+pub struct MazeSeed(pub u64);
+
 #[derive(Component)]
 pub struct Wall {
     pub wall_type: WallType,
@@ -31,6 +37,13 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
     pub fn offset(&self) -> (i32, i32) {
         match self {
             Direction::Up => (0, 1),
@@ -62,12 +75,64 @@ impl From<Direction> for WallType {
     }
 }
 
-pub fn setup_walls(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    let mut rng = rand::thread_rng();
+/// Which walls the current maze kept, for anything that needs to find its
+/// way through it without looking at wall entities.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Maze {
+    walls: HashSet<(usize, usize, Direction)>,
+}
+
+impl Maze {
+    /// Whether a tank can drive from cell `(x, y)` into its neighbour in `direction`.
+    pub fn is_open(&self, x: usize, y: usize, direction: Direction) -> bool {
+        let (dx, dy) = direction.offset();
+        is_within_bounds(x as i32 + dx, y as i32 + dy) && !self.walls.contains(&(x, y, direction))
+    }
+
+    /// Cells that can be driven to from `(x, y)` in one step.
+    pub fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        Direction::ALL
+            .into_iter()
+            .filter(move |direction| self.is_open(x, y, *direction))
+            .map(move |direction| {
+                let (dx, dy) = direction.offset();
+                ((x as i32 + dx) as usize, (y as i32 + dy) as usize)
+            })
+    }
+
+    /// Shortest way from cell `from` to cell `to`, both included, found
+    /// breadth first. `None` if `to` can't be reached.
+    pub fn path(&self, from: (usize, usize), to: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let mut came_from = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        came_from.insert(from, from);
+
+        while let Some(cell) = queue.pop_front() {
+            if cell == to {
+                let mut path = vec![cell];
+                let mut cell = cell;
+                while cell != from {
+                    cell = came_from[&cell];
+                    path.push(cell);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.neighbours(cell.0, cell.1) {
+                if let Entry::Vacant(entry) = came_from.entry(next) {
+                    entry.insert(cell);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Carves the maze for `seed`. The same seed always gives the same maze, the
+/// server keeps a copy of this to know the maze its clients are playing in.
+pub fn generate_maze(seed: u64) -> Maze {
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut frontier = vec![];
     let mut visited =
         vec![vec![false; GRID_CELL_VERTICAL_AMOUNT as usize]; GRID_CELL_HORIZONTAL_AMOUNT as usize];
@@ -75,20 +140,12 @@ pub fn setup_walls(
 
     for x in 0..GRID_CELL_HORIZONTAL_AMOUNT as usize {
         for y in 0..GRID_CELL_VERTICAL_AMOUNT as usize {
-            for direction in [
-                Direction::Up,
-                Direction::Down,
-                Direction::Left,
-                Direction::Right,
-            ] {
+            for direction in Direction::ALL {
                 walls.insert((x, y, direction));
             }
         }
     }
 
-    let horizontal_wall_mesh: Handle<Mesh> = meshes.add(Rectangle::new(GRID_CELL_SIZE, 5.0)).into();
-    let vertical_wall_mesh: Handle<Mesh> = meshes.add(Rectangle::new(5.0, GRID_CELL_SIZE)).into();
-
     let start_x = 0;
     let start_y = 0;
     mark_cell_as_maze(start_x, start_y, &mut visited, &mut frontier);
@@ -106,10 +163,25 @@ pub fn setup_walls(
         frontier.shuffle(&mut rng);
     }
 
+    Maze { walls }
+}
+
+pub fn setup_walls(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    seed: Res<MazeSeed>,
+) {
+    let maze = generate_maze(seed.0);
+
+    let horizontal_wall_mesh: Handle<Mesh> = meshes.add(Rectangle::new(GRID_CELL_SIZE, 5.0)).into();
+    let vertical_wall_mesh: Handle<Mesh> = meshes.add(Rectangle::new(5.0, GRID_CELL_SIZE)).into();
+
     let material = materials.add(Color::srgb(1.0, 1.0, 1.0));
+    let mut wall_grid = WallGrid::default();
 
-    for (x, y, direction) in walls.iter() {
-        place_wall(
+    for (x, y, direction) in maze.walls.iter() {
+        let wall = place_wall(
             &mut commands,
             &horizontal_wall_mesh,
             &vertical_wall_mesh,
@@ -118,7 +190,13 @@ pub fn setup_walls(
             *y,
             *direction,
         );
+        if let Some(wall) = wall {
+            wall_grid.insert(*x, *y, wall);
+        }
     }
+
+    commands.insert_resource(wall_grid);
+    commands.insert_resource(maze);
 }
 fn place_wall(
     commands: &mut Commands,
@@ -128,9 +206,9 @@ fn place_wall(
     x: usize,
     y: usize,
     direction: Direction,
-) {
-    let cell_x_center = -GAME_FIELD_WIDTH / 2.0 + GRID_CELL_SIZE * x as f32 + GRID_CELL_SIZE / 2.0;
-    let cell_y_center = -GAME_FIELD_HEIGHT / 2.0 + GRID_CELL_SIZE * y as f32 + GRID_CELL_SIZE / 2.0;
+) -> Option<Entity> {
+    let cell = cell_center(x, y);
+    let (cell_x_center, cell_y_center) = (cell.x, cell.y);
 
     let wall_pos = match direction {
         Direction::Up => (cell_x_center, cell_y_center + GRID_CELL_SIZE / 2.0),
@@ -154,21 +232,8 @@ fn place_wall(
             };
             (vertical_wall_mesh, aabb)
         };
-        println!("wall center {center}");
-        //commands.spawn(Text2dBundle {
-        //    transform: Transform::from_xyz(center.x, center.y, 1.),
-        //    text: Text::from_section(
-        //        format!("{}", center),
-        //        TextStyle {
-        //            color: Color::srgb(230., 100., 100.),
-        //            font_size: 10.,
-        //            ..default()
-        //        },
-        //    ),
-        //    ..default()
-        //});
-
-        commands
+
+        let wall = commands
             .spawn((
                 Mesh2d(wall_mesh.clone()),
                 MeshMaterial2d(matrial.clone()),
@@ -180,7 +245,12 @@ fn place_wall(
             })
             .insert(Static)
             .insert(direction)
-            .insert(Collider::cuboid(aabb.half_extents.x, aabb.half_extents.y));
+            .insert(Collider::cuboid(aabb.half_extents.x, aabb.half_extents.y))
+            .insert(collider::Collider::Aabb(aabb))
+            .id();
+        Some(wall)
+    } else {
+        None
     }
 }
 
@@ -192,12 +262,7 @@ fn mark_cell_as_maze(
 ) {
     visited[x][y] = true;
 
-    for direction in [
-        Direction::Up,
-        Direction::Down,
-        Direction::Left,
-        Direction::Right,
-    ] {
+    for direction in Direction::ALL {
         let (dx, dy) = direction.offset();
         let nx = x as i32 + dx;
         let ny = y as i32 + dy;
@@ -213,12 +278,7 @@ fn mark_cell_as_maze(
 
 fn get_maze_neighbors(x: usize, y: usize, visited: &[Vec<bool>]) -> Vec<(usize, usize, Direction)> {
     let mut neighbors = Vec::new();
-    for direction in [
-        Direction::Up,
-        Direction::Down,
-        Direction::Left,
-        Direction::Right,
-    ] {
+    for direction in Direction::ALL {
         let (dx, dy) = direction.offset();
         let nx = x as i32 + dx;
         let ny = y as i32 + dy;